@@ -1,13 +1,28 @@
 use std::{path::{PathBuf, Path}, fs};
 
+use chrono::{NaiveDate, NaiveDateTime, DateTime, Local, Datelike, Timelike};
 use json::{JsonValue, stringify_pretty};
 use proton_finder::GameDrive;
 
-use crate::State;
+use crate::{State, error::{Error, Result}};
 
 pub mod livery_ops;
 pub mod menu_changer;
 pub mod app_data;
+pub mod library;
+pub mod cleanup;
+pub mod orphans;
+pub mod install;
+pub mod archive;
+pub mod content_index;
+pub mod duplicates;
+pub mod transaction;
+pub mod backup;
+pub mod history;
+pub mod export;
+pub mod manifest;
+pub mod verify;
+pub mod tool_config;
 
 //Folder Strcuture in ACC:
 //User/Documents
@@ -68,24 +83,19 @@ pub fn get_acc_folder() -> Result<Option<PathBuf>,Option<PathBuf>> {
     }
 }
 
-fn get_config_file(state: &State, foldername: &str, filename: &str) -> Option<(PathBuf, JsonValue)> {
+fn get_config_file(state: &State, foldername: &str, filename: &str) -> Result<(PathBuf, JsonValue)> {
     let mut folder = state.root_folder.clone();
 
     folder.push(foldername);
-    
-
-    if folder.exists() {
-        folder.push(filename);
-        folder.set_extension(FILE_ENDING);
+    folder.push(filename);
+    folder.set_extension(FILE_ENDING);
 
-        if folder.exists() {
-            if let Ok(content) = read_json(folder.as_path()) {
-                return Some((folder, content));
-            }
-        }
+    if !folder.exists() {
+        return Err(Error::NotFound(format!("{}/{}.{}", foldername, filename, FILE_ENDING)));
     }
 
-    None
+    let content = read_json(folder.as_path())?;
+    Ok((folder, content))
 }
 
 pub fn read_json_from_bytes(data: Vec<u8>) -> json::Result<JsonValue> {
@@ -100,21 +110,59 @@ pub fn read_json_from_bytes(data: Vec<u8>) -> json::Result<JsonValue> {
     Err(json::Error::WrongType("File System Error".to_string()))
 }
 
-pub fn read_json(file: &Path) -> json::Result<JsonValue>{
-    
-    if let Ok(read) = fs::read_to_string(file) {
-        return json::parse(read.as_str());
+pub fn read_json(file: &Path) -> Result<JsonValue> {
+    let read = fs::read_to_string(file)?;
+
+    json::parse(read.as_str()).map_err(|e| Error::Json(format!("{}: {}", get_filename(file), e)))
+}
+
+pub fn write_json(file: &Path, data: JsonValue) -> Result<()> {
+    Ok(fs::write(file, stringify_pretty(data, 4))?)
+}
+
+/// The last component of the path, the whole path if it has none (e.g. `..` or `/`)
+pub fn get_filename(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => path.to_string_lossy().to_string()
     }
+}
 
-    Err(json::Error::WrongType("File System Error".to_string()))
+/// Last modified time of a file in local time
+pub fn get_modified(path: &Path) -> Option<NaiveDateTime> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+
+    Some(DateTime::<Local>::from(modified).naive_local())
+}
+
+/// Zip files store the modified time without timezone, so it is treated as local time
+pub fn zip_datetime_to_naive(time: zip::DateTime) -> Option<NaiveDateTime> {
+    NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)?
+        .and_hms_opt(time.hour() as u32, time.minute() as u32, time.second() as u32)
 }
 
-pub fn write_json(file: &Path, data: JsonValue) -> Result<(), std::io::Error> {
-    fs::write(file, stringify_pretty(data, 4))
+/// Zip only supports years 1980 to 2107, None if outside of that
+pub fn naive_to_zip_datetime(time: NaiveDateTime) -> Option<zip::DateTime> {
+    zip::DateTime::from_date_and_time(time.year().try_into().ok()?, time.month() as u8, time.day() as u8,
+        time.hour() as u8, time.minute() as u8, time.second() as u8).ok()
 }
 
-pub fn get_filename(path: &PathBuf) -> String {
-    path.file_name().expect("there must be at least a file name").to_str().expect("osstr to str should always work").to_string()
+/// Formats a byte count for displaying, e.g. 1.5 MB
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, UNITS[unit])
 }
 
 trait SafeRead {
@@ -2,7 +2,7 @@ use std::{path::PathBuf, fs};
 
 use serde::{Serialize, Deserialize};
 
-use crate::State;
+use crate::{State, error::Result};
 
 use super::menu_changer::{GraphicSettings, AudioSettings, self};
 
@@ -12,7 +12,9 @@ pub const SETTINGS_FILE: &str = "settings.json";
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Settings {
     livery_mode_settings: MenuSettings,
-    backup_settings: Option<MenuSettings>
+    backup_settings: Option<MenuSettings>,
+    #[serde(default)]
+    clean_keep_list: Vec<String>
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,48 +46,34 @@ fn get_file_path(file: FileType, state: &State) -> Option<PathBuf> {
     None
 }
 
-pub fn get_settings(state: &State) -> Option<Settings> {
+pub fn get_settings(state: &State) -> Result<Settings> {
     if let Some(path) = get_file_path(FileType::MainSettings, state) {
-        if let Ok(data) = fs::read_to_string(path.as_path()) {
-            if let Ok(settings) = serde_json::from_str(data.as_str()) {
-                return Some(settings);
-            }
-        }
+        let data = fs::read_to_string(path.as_path())?;
+        Ok(serde_json::from_str(data.as_str())?)
     } else {
         // Defaults don't exist, so lets generate those
-        if !generate(state) {
-            return None;
-        }
-        return get_settings(state);
+        generate(state)?;
+        get_settings(state)
     }
-
-    None
 }
 
-pub fn write_settings(settings: Settings, state: &State) -> bool {
+pub fn write_settings(settings: Settings, state: &State) -> Result<()> {
     if let Some(path) = get_file_path(FileType::MainSettings, state) {
-        if let Ok(data) = serde_json::to_string_pretty(&settings) {
-            return fs::write(path.as_path(), data).is_ok();
-        }
+        let data = serde_json::to_string_pretty(&settings)?;
+        Ok(fs::write(path.as_path(), data)?)
     } else {
         // Defaults don't exist, so lets generate those
-        if !generate(state) {
-            return false;
-        }
-        return write_settings(settings, state);
+        generate(state)?;
+        write_settings(settings, state)
     }
-
-    false
 }
 
-pub fn generate(state: &State) -> bool {
+pub fn generate(state: &State) -> Result<()> {
     let mut folder = state.root_folder.clone();
 
     folder.push(ACC_APP_FOLDER_NAME);
 
-    if fs::create_dir_all(folder.as_path()).is_err() {
-        return false;
-    }
+    fs::create_dir_all(folder.as_path())?;
 
     // Settings file
     let mut sett_file = folder.clone();
@@ -97,45 +85,55 @@ pub fn generate(state: &State) -> bool {
             graphic: GraphicSettings { resolution: (1600, 900), fullscreen: false },
             audio: AudioSettings { master: 0.5, music: 0.0 }
         },
-        backup_settings: None
+        backup_settings: None,
+        clean_keep_list: Vec::<String>::new()
     };
 
-    if let Ok(data) = serde_json::to_string_pretty(&default_settings) {
-        if fs::write(sett_file, data).is_err() {
-            return false;
-        }
-    }
+    let data = serde_json::to_string_pretty(&default_settings)?;
+    fs::write(sett_file, data)?;
+
+    Ok(())
+}
 
-    true
+/// Switches livery mode and saves the settings, returns if livery mode is on now
+pub fn toggle_liverymode(state: &State) -> Result<bool> {
+    let mut settings = get_settings(state)?;
+    let mode = settings.switch_liverymode(state)?;
+    write_settings(settings, state)?;
+
+    Ok(mode)
 }
 
 impl Settings {
-    pub fn switch_liverymode(&mut self, state: &State) -> Option<bool> {
+    /// Returns if livery mode is on after switching
+    pub fn switch_liverymode(&mut self, state: &State) -> Result<bool> {
         if let Some(backup) = self.backup_settings.clone() {
             // A backup exists, therefore this is to exit liverymode
-            if menu_changer::set_dds_generation(backup.dds_generation, state).is_some() && menu_changer::set_graphic_settings(backup.graphic, state).is_some()
-                    && menu_changer::set_audio_settings(backup.audio, state).is_some() {
-                        self.backup_settings = None;
-                        return Some(false);
-            }
+            menu_changer::set_dds_generation(backup.dds_generation, state)?;
+            menu_changer::set_graphic_settings(backup.graphic, state)?;
+            menu_changer::set_audio_settings(backup.audio, state)?;
+
+            self.backup_settings = None;
+            Ok(false)
         } else {
             //We are entering liverymode
-            let res = Some(MenuSettings {
+            let old_settings = MenuSettings {
                 dds_generation: menu_changer::set_dds_generation(self.livery_mode_settings.dds_generation, state)?,
                 audio: menu_changer::set_audio_settings(self.livery_mode_settings.audio.clone(), state)?,
                 graphic: menu_changer::set_graphic_settings(self.livery_mode_settings.graphic.clone(), state)?
-            });
+            };
 
-            if let Some(old_settings) = res {
-                self.backup_settings = Some(old_settings);
-                return Some(true)
-            }        
+            self.backup_settings = Some(old_settings);
+            Ok(true)
         }
-        
-        None
     }
 
     pub fn is_in_liverymode(&self) -> bool {
         self.backup_settings.is_some()
     }
+
+    /// Livery folders the clean up will never touch (e.g. ones shipped with pre-baked dds files)
+    pub fn get_clean_keep_list(&self) -> &Vec<String> {
+        &self.clean_keep_list
+    }
 }
@@ -0,0 +1,321 @@
+use std::{path::Path, fs::File, io::{self, BufReader, Read, Seek, Write}, time::{SystemTime, UNIX_EPOCH, Duration}};
+
+use chrono::{NaiveDateTime, DateTime, Local, TimeZone};
+use indicatif::ProgressBar;
+
+use crate::error::{Error, Result};
+
+/// Archive formats livery packs can be imported from and exported to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    Zip,
+    SevenZ,
+    Tar,
+    TarGz,
+    TarZst
+}
+
+/// How files are compressed within zip archives
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompressionMethod {
+    Store,
+    Deflate,
+    Bzip2,
+    Zstd
+}
+
+/// Compression settings of an export
+/// zip archives choose the method per file extension, the other formats compress the whole archive and only use the level
+#[derive(Debug, Clone)]
+pub struct CompressionOptions {
+    pub method: CompressionMethod,
+    /// Methods for file extensions (without the dot), later entries win
+    pub per_extension: Vec<(String, CompressionMethod)>,
+    /// Depends on the method, e.g. 0-9 for deflate and 7z, 1-22 for zstd, None is the default of the method
+    pub level: Option<i32>
+}
+
+impl Default for CompressionOptions {
+    /// png and dds are compressed already, so they are only stored
+    fn default() -> Self {
+        CompressionOptions {
+            method: CompressionMethod::Deflate,
+            per_extension: vec![("png".to_string(), CompressionMethod::Store), ("dds".to_string(), CompressionMethod::Store)],
+            level: None
+        }
+    }
+}
+
+impl CompressionOptions {
+    pub fn get_method(&self, file_name: &str) -> CompressionMethod {
+        let lower = file_name.to_lowercase();
+
+        for (extension, method) in self.per_extension.iter().rev() {
+            if lower.ends_with(format!(".{}", extension.to_lowercase().trim_start_matches('.')).as_str()) {
+                return *method;
+            }
+        }
+
+        self.method
+    }
+}
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const ZIP_EMPTY_MAGIC: &[u8] = b"PK\x05\x06";
+const SEVENZ_MAGIC: &[u8] = b"7z\xBC\xAF\x27\x1C";
+const GZIP_MAGIC: &[u8] = b"\x1F\x8B";
+const ZSTD_MAGIC: &[u8] = b"\x28\xB5\x2F\xFD";
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_MAGIC_OFFSET: usize = 257;
+
+impl ArchiveFormat {
+    /// Detects the format by the magic bytes at the start of the data
+    /// gzip and zstd streams are assumed to contain a tar
+    pub fn detect(data: &[u8]) -> Option<ArchiveFormat> {
+        if data.starts_with(ZIP_MAGIC) || data.starts_with(ZIP_EMPTY_MAGIC) {
+            Some(ArchiveFormat::Zip)
+        } else if data.starts_with(SEVENZ_MAGIC) {
+            Some(ArchiveFormat::SevenZ)
+        } else if data.starts_with(GZIP_MAGIC) {
+            Some(ArchiveFormat::TarGz)
+        } else if data.starts_with(ZSTD_MAGIC) {
+            Some(ArchiveFormat::TarZst)
+        } else if data.len() > TAR_MAGIC_OFFSET + TAR_MAGIC.len() && &data[TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()] == TAR_MAGIC {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::SevenZ => "7z",
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarZst => "tar.zst"
+        }
+    }
+}
+
+/// Removes a known archive extension (including the double ones like .tar.gz) from a file name
+pub fn strip_extension(file_name: &str) -> String {
+    let lower = file_name.to_lowercase();
+
+    for ext in [".tar.gz", ".tgz", ".tar.zst", ".tar", ".zip", ".7z"] {
+        if lower.ends_with(ext) {
+            return file_name[..file_name.len() - ext.len()].to_string();
+        }
+    }
+
+    file_name.to_string()
+}
+
+fn to_archive_error<E: ToString>(e: E) -> Error {
+    Error::InvalidArchive(e.to_string())
+}
+
+fn system_time_to_naive(time: SystemTime) -> NaiveDateTime {
+    DateTime::<Local>::from(time).naive_local()
+}
+
+/// Detects the format of an archive file, reading only the header
+pub fn detect_file(path: &Path) -> Option<ArchiveFormat> {
+    let mut header = Vec::<u8>::new();
+    File::open(path).ok()?.take(TAR_MAGIC_OFFSET as u64 + 8).read_to_end(&mut header).ok()?;
+
+    ArchiveFormat::detect(header.as_slice())
+}
+
+/// Streams through all files of an archive in a single pass, folders are skipped
+/// `each` gets the entry name, a reader for the content and the modified time, the progressbar is advanced per entry
+pub fn for_each_entry<F: FnMut(&str, &mut dyn Read, Option<NaiveDateTime>) -> io::Result<()>>(path: &Path, progressbar: &ProgressBar, each: F) -> Result<()> {
+    let file = BufReader::new(File::open(path)?);
+
+    match detect_file(path) {
+        Some(ArchiveFormat::Zip) => read_zip(file, progressbar, each),
+        Some(ArchiveFormat::SevenZ) => read_sevenz(path, progressbar, each),
+        Some(ArchiveFormat::Tar) => read_tar(file, progressbar, each),
+        Some(ArchiveFormat::TarGz) => read_tar(flate2::bufread::GzDecoder::new(file), progressbar, each),
+        Some(ArchiveFormat::TarZst) => read_tar(zstd::stream::read::Decoder::with_buffer(file)?, progressbar, each),
+        None => Err(Error::InvalidArchive(format!("{} is not a known archive format", path.display())))
+    }
+}
+
+fn read_zip<R: Read + Seek, F: FnMut(&str, &mut dyn Read, Option<NaiveDateTime>) -> io::Result<()>>(stream: R, progressbar: &ProgressBar, mut each: F) -> Result<()> {
+    // Only reads the central directory, the entries are then decompressed one after another
+    let mut zip_content = zip::ZipArchive::new(stream)?;
+    progressbar.inc_length(zip_content.len() as u64);
+
+    for index in 0..zip_content.len() {
+        progressbar.inc(1);
+
+        let mut entry = zip_content.by_index(index)?;
+        if entry.is_dir() {
+            continue;
+        }
+
+        let name = entry.name().to_string();
+        let modified = super::zip_datetime_to_naive(entry.last_modified());
+        each(&name, &mut entry, modified)?;
+    }
+
+    Ok(())
+}
+
+fn read_sevenz<F: FnMut(&str, &mut dyn Read, Option<NaiveDateTime>) -> io::Result<()>>(path: &Path, progressbar: &ProgressBar, mut each: F) -> Result<()> {
+    let mut reader = sevenz_rust::SevenZReader::open(path, sevenz_rust::Password::empty()).map_err(to_archive_error)?;
+    progressbar.inc_length(reader.archive().files.len() as u64);
+
+    reader.for_each_entries(|entry, content| {
+        progressbar.inc(1);
+        if entry.is_directory() {
+            return Ok(true);
+        }
+
+        let modified = if entry.has_last_modified_date {
+            Some(system_time_to_naive(SystemTime::from(entry.last_modified_date())))
+        } else {
+            None
+        };
+        each(entry.name(), content, modified)?;
+
+        Ok(true)
+    }).map_err(to_archive_error)
+}
+
+fn read_tar<R: Read, F: FnMut(&str, &mut dyn Read, Option<NaiveDateTime>) -> io::Result<()>>(stream: R, progressbar: &ProgressBar, mut each: F) -> Result<()> {
+    let mut archive = tar::Archive::new(stream);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        // The number of entries in a tar is unknown until we reached the end
+        progressbar.inc_length(1);
+        progressbar.inc(1);
+
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let name = String::from_utf8_lossy(entry.path_bytes().as_ref()).to_string();
+        let modified = entry.header().mtime().ok().map(|mtime| system_time_to_naive(UNIX_EPOCH + Duration::from_secs(mtime)));
+        each(&name, &mut entry, modified)?;
+    }
+
+    Ok(())
+}
+
+/// Writes archives of any ArchiveFormat, entries are added one by one
+pub enum ArchiveWriter {
+    Zip(zip::ZipWriter<File>, CompressionOptions),
+    SevenZ(sevenz_rust::SevenZWriter<File>),
+    Tar(tar::Builder<File>),
+    // The compressed tars keep their encoder type, so finishing the stream can report errors
+    TarGz(tar::Builder<flate2::write::GzEncoder<File>>),
+    TarZst(tar::Builder<zstd::stream::write::Encoder<'static, File>>)
+}
+
+impl ArchiveWriter {
+    pub fn create(path: &Path, format: ArchiveFormat, compression: &CompressionOptions) -> Result<ArchiveWriter> {
+        let file = File::create(path)?;
+
+        Ok(match format {
+            ArchiveFormat::Zip => ArchiveWriter::Zip(zip::ZipWriter::new(file), compression.clone()),
+            ArchiveFormat::SevenZ => {
+                let mut writer = sevenz_rust::SevenZWriter::new(file).map_err(to_archive_error)?;
+                if let Some(level) = compression.level {
+                    writer.set_content_methods(vec![sevenz_rust::lzma::LZMA2Options::with_preset(level.clamp(0, 9) as u32).into()]);
+                }
+                ArchiveWriter::SevenZ(writer)
+            },
+            ArchiveFormat::Tar => ArchiveWriter::Tar(tar::Builder::new(file)),
+            ArchiveFormat::TarGz => {
+                let level = compression.level.map(|level| flate2::Compression::new(level.clamp(0, 9) as u32)).unwrap_or_default();
+                ArchiveWriter::TarGz(tar::Builder::new(flate2::write::GzEncoder::new(file, level)))
+            },
+            ArchiveFormat::TarZst => ArchiveWriter::TarZst(tar::Builder::new(zstd::stream::write::Encoder::new(file, compression.level.unwrap_or(0))?))
+        })
+    }
+
+    pub fn add_file(&mut self, name: &str, data: &[u8], modified: Option<NaiveDateTime>) -> Result<()> {
+        self.add_reader(name, data, data.len() as u64, modified)
+    }
+
+    /// Adds an entry streamed from the reader, `size` has to be the number of bytes it returns
+    pub fn add_reader<R: Read>(&mut self, name: &str, mut reader: R, size: u64, modified: Option<NaiveDateTime>) -> Result<()> {
+        let modified = modified.and_then(|time| Local.from_local_datetime(&time).single());
+
+        match self {
+            ArchiveWriter::Zip(writer, compression) => {
+                let method = match compression.get_method(name) {
+                    CompressionMethod::Store => zip::CompressionMethod::Stored,
+                    CompressionMethod::Deflate => zip::CompressionMethod::Deflated,
+                    CompressionMethod::Bzip2 => zip::CompressionMethod::Bzip2,
+                    CompressionMethod::Zstd => zip::CompressionMethod::Zstd
+                };
+                // Out of range levels are an error in zip, so they are clamped to what the method supports
+                let level = compression.level.and_then(|level| match method {
+                    zip::CompressionMethod::Zstd => Some(level.clamp(-7, 22)),
+                    zip::CompressionMethod::Stored => None,
+                    zip::CompressionMethod::Bzip2 => Some(level.clamp(1, 9)),
+                    _ => Some(level.clamp(0, 9))
+                });
+
+                let mut options = zip::write::FileOptions::default().compression_method(method).compression_level(level);
+                if let Some(time) = modified.and_then(|time| super::naive_to_zip_datetime(time.naive_local())) {
+                    options = options.last_modified_time(time);
+                }
+
+                writer.start_file(name, options)?;
+                io::copy(&mut reader, writer)?;
+            },
+            ArchiveWriter::SevenZ(writer) => {
+                let mut entry = sevenz_rust::SevenZArchiveEntry::new();
+                entry.name = name.to_string();
+                entry.has_stream = true;
+                if let Some(time) = modified.and_then(|time| SystemTime::from(time).try_into().ok()) {
+                    entry.has_last_modified_date = true;
+                    entry.last_modified_date = time;
+                }
+
+                writer.push_archive_entry(entry, Some(reader)).map_err(to_archive_error)?;
+            },
+            ArchiveWriter::Tar(builder) => append_tar(builder, name, reader, size, modified)?,
+            ArchiveWriter::TarGz(builder) => append_tar(builder, name, reader, size, modified)?,
+            ArchiveWriter::TarZst(builder) => append_tar(builder, name, reader, size, modified)?
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        match self {
+            ArchiveWriter::Zip(mut writer, _) => {
+                writer.finish()?;
+            },
+            ArchiveWriter::SevenZ(writer) => {
+                writer.finish()?;
+            },
+            ArchiveWriter::Tar(builder) => {
+                builder.into_inner()?.flush()?;
+            },
+            ArchiveWriter::TarGz(builder) => {
+                builder.into_inner()?.finish()?.flush()?;
+            },
+            ArchiveWriter::TarZst(builder) => {
+                builder.into_inner()?.finish()?.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn append_tar<W: Write, R: Read>(builder: &mut tar::Builder<W>, name: &str, reader: R, size: u64, modified: Option<DateTime<Local>>) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(modified.map(|time| time.timestamp().max(0) as u64).unwrap_or(0));
+
+    builder.append_data(&mut header, name, reader)
+}
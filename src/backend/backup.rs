@@ -0,0 +1,190 @@
+use std::{path::{Path, PathBuf}, fs, io};
+
+use chrono::Local;
+use indicatif::ProgressBar;
+use serde::{Serialize, Deserialize};
+
+use crate::{State, error::{Error, Result}};
+
+use super::{app_data::ACC_APP_FOLDER_NAME, livery_ops::{self, ACC_CUSTOMS_FOLDER_NAME, ACC_LIVERY_FOLDER_NAME}, archive::{self, ArchiveFormat, ArchiveWriter, CompressionOptions}, DATE_FORMAT_STR};
+
+pub const BACKUP_FOLDER: &str = "backups";
+pub const JOURNAL_FILE: &str = "install_journal.json";
+
+/// One install, with everything needed to undo it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: usize,
+    pub time: String,
+    /// The archive or folder that was installed
+    pub source: String,
+    /// Files (as path within Customs) that did not exist before
+    pub created: Vec<String>,
+    /// Files (as path within Customs) that were replaced, their previous version is in the backup
+    pub replaced: Vec<String>,
+    /// File name of the backup archive within the backup folder
+    pub backup: Option<String>,
+    #[serde(default)]
+    pub undone: bool
+}
+
+fn get_app_folder(state: &State) -> PathBuf {
+    let mut path = state.root_folder.clone();
+    path.push(ACC_APP_FOLDER_NAME);
+
+    path
+}
+
+pub fn get_backup_folder(state: &State) -> PathBuf {
+    let mut path = get_app_folder(state);
+    path.push(BACKUP_FOLDER);
+
+    path
+}
+
+/// Reads the install journal, oldest entry first, empty if there is none yet
+/// A damaged journal is an error, so it is never overwritten (which would lose every earlier undo record)
+pub fn get_journal(state: &State) -> Result<Vec<JournalEntry>> {
+    let mut path = get_app_folder(state);
+    path.push(JOURNAL_FILE);
+
+    match fs::read_to_string(&path) {
+        Ok(data) => serde_json::from_str(data.as_str()).map_err(|e| Error::Json(format!("{} can't be read ({}), fix or remove it", path.display(), e))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into())
+    }
+}
+
+fn write_journal(journal: &[JournalEntry], state: &State) -> Result<()> {
+    let mut path = get_app_folder(state);
+    fs::create_dir_all(&path)?;
+    path.push(JOURNAL_FILE);
+
+    let data = serde_json::to_string_pretty(journal)?;
+    Ok(fs::write(path, data)?)
+}
+
+/// Turns a path within the Customs folder back into the full path, refusing anything that leaves it
+fn get_customs_path(internal_path: &str, state: &State) -> Result<PathBuf> {
+    let mut path = state.root_folder.clone();
+    path.push(ACC_CUSTOMS_FOLDER_NAME);
+
+    for component in internal_path.split('/') {
+        if !livery_ops::is_safe_name(component) {
+            return Err(Error::Json(format!("invalid path {} in {}", internal_path, JOURNAL_FILE)));
+        }
+        path.push(component);
+    }
+
+    Ok(path)
+}
+
+/// Saves the replaced files into a backup archive and adds the install to the journal
+/// `replaced` are pairs of the path within Customs and where the previous version currently is
+pub fn record(source: &str, created: Vec<String>, replaced: Vec<(String, PathBuf)>, state: &State) -> Result<JournalEntry> {
+    let mut journal = get_journal(state)?;
+    let id = journal.last().map(|entry| entry.id + 1).unwrap_or(1);
+    let now = Local::now();
+
+    let backup = if replaced.is_empty() {
+        None
+    } else {
+        let folder = get_backup_folder(state);
+        fs::create_dir_all(&folder)?;
+
+        let name = format!("{}-{}-{}.{}", now.format(DATE_FORMAT_STR), now.format("%H%M%S"), id, ArchiveFormat::Zip.get_extension());
+        let mut writer = ArchiveWriter::create(&folder.join(&name), ArchiveFormat::Zip, &CompressionOptions::default())?;
+        for (internal_path, previous) in replaced.iter() {
+            writer.add_file(internal_path, fs::read(previous)?.as_slice(), super::get_modified(previous))?;
+        }
+        writer.finish()?;
+
+        Some(name)
+    };
+
+    let entry = JournalEntry {
+        id,
+        time: now.format(&format!("{} %H:%M:%S", DATE_FORMAT_STR)).to_string(),
+        source: source.to_string(),
+        created,
+        replaced: replaced.into_iter().map(|(internal_path, _)| internal_path).collect(),
+        backup,
+        undone: false
+    };
+
+    journal.push(entry.clone());
+    write_journal(&journal, state)?;
+
+    Ok(entry)
+}
+
+/// The last `count` installs that were not undone yet, newest first
+pub fn get_undoable(count: usize, state: &State) -> Result<Vec<JournalEntry>> {
+    Ok(get_journal(state)?.into_iter().rev().filter(|entry| !entry.undone).take(count).collect())
+}
+
+/// Reverts an install: created files are deleted and replaced ones restored from the backup
+pub fn undo(entry: &JournalEntry, state: &State) -> Result<()> {
+    // Read first, if the install can't be marked as undone nothing is touched
+    let mut journal = get_journal(state)?;
+
+    for internal_path in entry.created.iter() {
+        let path = get_customs_path(internal_path, state)?;
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+
+        // Livery folders created by the install go as well, if they are empty now (but never Cars or Liveries itself)
+        let components: Vec<&str> = internal_path.split('/').collect();
+        if let (Some(parent), [ACC_LIVERY_FOLDER_NAME, _, _]) = (path.parent(), components.as_slice()) {
+            if parent.read_dir().map(|mut content| content.next().is_none()).unwrap_or(false) {
+                fs::remove_dir(parent)?;
+            }
+        }
+    }
+
+    if let Some(backup) = &entry.backup {
+        let mut restored = 0;
+        archive::for_each_entry(&get_backup_folder(state).join(backup), &ProgressBar::hidden(), |name, content, _| {
+            let path = get_customs_path(name, state).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let mut data = Vec::<u8>::new();
+            content.read_to_end(&mut data)?;
+            fs::write(path, data)?;
+            restored += 1;
+
+            Ok(())
+        })?;
+
+        if restored != entry.replaced.len() {
+            return Err(Error::InvalidArchive(format!("backup {} is incomplete", backup)));
+        }
+    }
+
+    for item in journal.iter_mut().filter(|item| item.id == entry.id) {
+        item.undone = true;
+    }
+    write_journal(&journal, state)
+}
+
+/// Removes an install from the journal together with its backup, for installs that were rolled back
+pub fn discard(entry: &JournalEntry, state: &State) -> Result<()> {
+    if let Some(backup) = &entry.backup {
+        fs::remove_file(get_backup_folder(state).join(backup))?;
+    }
+
+    let mut journal = get_journal(state)?;
+    journal.retain(|item| item.id != entry.id);
+    write_journal(&journal, state)
+}
+
+/// Path of a file relative to the Customs folder, with / as separator
+pub fn get_internal_path(path: &Path, state: &State) -> Option<String> {
+    let customs = state.root_folder.join(ACC_CUSTOMS_FOLDER_NAME);
+    let relative = path.strip_prefix(customs).ok()?;
+
+    Some(relative.components().map(|component| component.as_os_str().to_string_lossy().to_string()).collect::<Vec<String>>().join("/"))
+}
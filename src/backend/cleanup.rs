@@ -0,0 +1,69 @@
+use std::{path::PathBuf, fs};
+
+use crate::{State, error::Result};
+
+use super::library;
+
+/// Suffix of the dds files generated when loading the showroom, which are useless
+pub const SHOWROOM_DDS_SUFFIX: &str = "_0.dds";
+/// Suffix of the dds files generated ingame, the game regenerates them, but it takes a while
+pub const INGAME_DDS_SUFFIX: &str = "_1.dds";
+
+/// Files that can be removed from a single livery folder
+#[derive(Debug, Clone)]
+pub struct CleanupEntry {
+    pub livery_folder: String,
+    pub files: Vec<PathBuf>,
+    pub size: u64
+}
+
+/// Checks if a file within a livery folder is regenerated by the game
+pub fn is_regenerable_dds(file_name: &str, include_ingame: bool) -> bool {
+    let name = file_name.to_lowercase();
+
+    name.ends_with(SHOWROOM_DDS_SUFFIX) || (include_ingame && name.ends_with(INGAME_DDS_SUFFIX))
+}
+
+/// Scans all livery folders for dds files that can be removed
+/// Folders on the keep list (compared case insensitive) are skipped, as are folders with nothing to remove
+pub fn scan(state: &State, include_ingame: bool, keep_list: &[String]) -> Vec<CleanupEntry> {
+    let mut output = Vec::<CleanupEntry>::new();
+
+    for livery_folder in library::get_all_livery_folders(state) {
+        if keep_list.iter().any(|keep| keep.to_lowercase() == livery_folder.to_lowercase()) {
+            continue;
+        }
+
+        let mut folder = library::get_liveries_folder(state);
+        folder.push(&livery_folder);
+
+        let mut entry = CleanupEntry { livery_folder, files: Vec::<PathBuf>::new(), size: 0 };
+
+        if let Ok(folder_content) = folder.read_dir() {
+            for item in folder_content.flatten() {
+                if let Ok(meta) = item.metadata() {
+                    if meta.is_file() && is_regenerable_dds(item.file_name().to_string_lossy().as_ref(), include_ingame) {
+                        entry.size += meta.len();
+                        entry.files.push(item.path());
+                    }
+                }
+            }
+        }
+
+        if !entry.files.is_empty() {
+            entry.files.sort();
+            output.push(entry);
+        }
+    }
+
+    output
+}
+
+/// Deletes all files listed in the entry
+pub fn remove(entry: &CleanupEntry) -> Result<()> {
+    for file in entry.files.iter() {
+        fs::remove_file(file)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use std::{path::{Path, PathBuf}, fs::{self, File}, io::Read, collections::HashMap, time::UNIX_EPOCH};
+
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+
+use crate::{State, error::Result};
+
+use super::{app_data::ACC_APP_FOLDER_NAME, livery_ops::ACC_CUSTOMS_FOLDER_NAME};
+
+pub const INDEX_FILE: &str = "content_index.json";
+
+/// What we know about an installed file, the hash is only trusted while size and modified time still match
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub size: u64,
+    /// Modified time as seconds and nanoseconds since the unix epoch
+    pub modified: (u64, u32),
+    pub hash: String
+}
+
+/// Persistent index of the hashes of all files within Customs, keyed by the path within the Customs folder (e.g. Liveries/Name/decals.png)
+/// Lives in the app folder, entries are (re)hashed lazily when they are requested and out of date
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContentIndex {
+    files: HashMap<String, IndexEntry>,
+    #[serde(skip)]
+    changed: bool
+}
+
+fn get_index_path(state: &State) -> PathBuf {
+    let mut path = state.root_folder.clone();
+    path.push(ACC_APP_FOLDER_NAME);
+    path.push(INDEX_FILE);
+
+    path
+}
+
+fn get_size_and_modified(path: &Path) -> Option<(u64, (u64, u32))> {
+    let meta = fs::metadata(path).ok()?;
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    Some((meta.len(), (modified.as_secs(), modified.subsec_nanos())))
+}
+
+/// Hashes a file without loading it fully into memory
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0_u8; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn hash_bytes(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+impl ContentIndex {
+    /// Loads the index, a missing or broken index file results in an empty index
+    pub fn load(state: &State) -> ContentIndex {
+        match fs::read_to_string(get_index_path(state)) {
+            Ok(data) => serde_json::from_str(data.as_str()).unwrap_or_default(),
+            Err(_) => ContentIndex::default()
+        }
+    }
+
+    /// Writes the index back, if anything changed
+    pub fn save(&mut self, state: &State) -> Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+
+        let path = get_index_path(state);
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+
+        let data = serde_json::to_string(&self)?;
+        fs::write(path, data)?;
+        self.changed = false;
+
+        Ok(())
+    }
+
+    /// Returns the hash of the installed file at this path within Customs, None if it doesn't exist
+    /// The file is only read if it is not indexed yet or changed since
+    pub fn get_hash(&mut self, internal_path: &str, state: &State) -> Option<String> {
+        let mut path = state.root_folder.clone();
+        path.push(ACC_CUSTOMS_FOLDER_NAME);
+        path.push(internal_path);
+
+        let (size, modified) = match get_size_and_modified(&path) {
+            Some(res) => res,
+            None => {
+                self.remove(internal_path);
+                return None;
+            }
+        };
+
+        if let Some(entry) = self.files.get(internal_path) {
+            if entry.size == size && entry.modified == modified {
+                return Some(entry.hash.clone());
+            }
+        }
+
+        let hash = hash_file(&path).ok()?;
+        self.files.insert(internal_path.to_string(), IndexEntry { size, modified, hash: hash.clone() });
+        self.changed = true;
+
+        Some(hash)
+    }
+
+    pub fn remove(&mut self, internal_path: &str) {
+        if self.files.remove(internal_path).is_some() {
+            self.changed = true;
+        }
+    }
+
+    /// Removes all entries of files that no longer exist
+    pub fn prune(&mut self, state: &State) {
+        let mut customs = state.root_folder.clone();
+        customs.push(ACC_CUSTOMS_FOLDER_NAME);
+
+        let before = self.files.len();
+        self.files.retain(|internal_path, _| customs.join(internal_path).is_file());
+        if self.files.len() != before {
+            self.changed = true;
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::{HashMap, BTreeMap};
+
+use crate::{State, model::CarJson, error::{Error, Result}};
+
+use super::{library, livery_ops, cleanup, content_index::ContentIndex};
+
+/// Liveries that are the same, either as livery folders with identical content or car.jsons that only differ in GUIDs
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    /// Livery folders with identical content or used by identical car.jsons, the first one is kept by default
+    pub folders: Vec<String>,
+    /// All car.jsons pointing to one of the folders
+    pub car_files: Vec<String>,
+    /// car.jsons that are identical to an earlier one in car_files (ignoring GUIDs and the livery folder)
+    pub duplicate_car_files: Vec<String>,
+    /// All folders have the same content, else only their car.jsons are identical
+    pub same_content: bool
+}
+
+/// Hash over all file names and their content within a livery folder
+/// dds files ACC generates itself are skipped, as they depend on when (and if) the livery was loaded ingame
+fn get_folder_fingerprint(folder: &str, state: &State, index: &mut ContentIndex) -> Option<String> {
+    let mut files = Vec::<String>::new();
+
+    for item in library::get_liveries_folder(state).join(folder).read_dir().ok()?.flatten() {
+        let name = item.file_name().to_string_lossy().to_string();
+        if !item.path().is_file() || cleanup::is_regenerable_dds(&name, true) {
+            continue;
+        }
+
+        let hash = index.get_hash(&format!("{}/{}/{}", livery_ops::ACC_LIVERY_FOLDER_NAME, folder, name), state)?;
+        files.push(format!("{}:{}", name.to_lowercase(), hash));
+    }
+
+    if files.is_empty() {
+        return None;
+    }
+
+    files.sort();
+    Some(super::content_index::hash_bytes(files.join("\n").as_bytes()))
+}
+
+/// The car.json without the fields that differ between copies of the same livery, independent of the key order
+fn get_car_key(car: &CarJson) -> Option<Vec<u8>> {
+    let fields: BTreeMap<String, serde_json::Value> = car.to_map()?.into_iter()
+        .filter(|(key, _)| !matches!(key.as_str(), "carGuid" | "teamGuid" | "customSkinName"))
+        .collect();
+
+    serde_json::to_vec(&fields).ok()
+}
+
+/// Finds the group of a folder, groups are merged by pointing one root to the other
+fn find_root(parents: &mut [usize], folder: usize) -> usize {
+    let mut root = folder;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[folder] = root;
+
+    root
+}
+
+fn join(parents: &mut [usize], first: usize, second: usize) {
+    let (first, second) = (find_root(parents, first), find_root(parents, second));
+    // The earlier folder stays the root, so it is the one kept by default
+    parents[first.max(second)] = first.min(second);
+}
+
+/// Groups all installed liveries with identical livery folders and/or identical car.jsons
+/// Folders end up in one group if their content is identical, or if car.jsons pointing to them are (e.g. the same livery installed twice under different names)
+pub fn find_duplicates(state: &State, index: &mut ContentIndex) -> Vec<DuplicateGroup> {
+    let installed = library::get_installed_liveries(state);
+    let folders = library::get_all_livery_folders(state);
+    let fingerprints: Vec<Option<String>> = folders.iter().map(|folder| get_folder_fingerprint(folder, state, index)).collect();
+    let mut parents: Vec<usize> = (0..folders.len()).collect();
+
+    // Folders with identical content
+    let mut by_fingerprint = HashMap::<&String, usize>::new();
+    for (position, fingerprint) in fingerprints.iter().enumerate() {
+        if let Some(fingerprint) = fingerprint {
+            let first = *by_fingerprint.entry(fingerprint).or_insert(position);
+            join(&mut parents, first, position);
+        }
+    }
+
+    // Folders used by identical car.jsons, sorted by name so e.g. #140_TeamIris.json comes before 140-230514-202639.json
+    let mut cars = Vec::<(String, usize, Option<Vec<u8>>)>::new();
+    let mut by_car_key = HashMap::<Vec<u8>, usize>::new();
+    for liver in installed {
+        let (car_file, car, folder) = match (liver.car_file, liver.car, liver.livery_folder) {
+            (Some(car_file), Some(car), Some(folder)) => (car_file, car, folder),
+            _ => continue
+        };
+        let position = match folders.iter().position(|item| item == &folder) {
+            Some(position) => position,
+            None => continue
+        };
+
+        let key = get_car_key(&car);
+        if let Some(key) = &key {
+            let first = *by_car_key.entry(key.clone()).or_insert(position);
+            join(&mut parents, first, position);
+        }
+        cars.push((car_file, position, key));
+    }
+
+    let mut groups = Vec::<DuplicateGroup>::new();
+    let mut group_fingerprints = Vec::<Option<&String>>::new();
+    let mut group_of_root = HashMap::<usize, usize>::new();
+    for (position, folder) in folders.iter().enumerate() {
+        let fingerprint = fingerprints[position].as_ref();
+        let group = *group_of_root.entry(find_root(&mut parents, position)).or_insert_with(|| {
+            groups.push(DuplicateGroup { folders: Vec::new(), car_files: Vec::new(), duplicate_car_files: Vec::new(), same_content: true });
+            group_fingerprints.push(fingerprint);
+            groups.len() - 1
+        });
+
+        if fingerprint.is_none() || group_fingerprints[group] != fingerprint {
+            groups[group].same_content = false;
+        }
+        groups[group].folders.push(folder.clone());
+    }
+
+    let mut seen_keys = Vec::<Vec<u8>>::new();
+    for (car_file, position, key) in cars {
+        let group = group_of_root[&find_root(&mut parents, position)];
+
+        match key {
+            Some(key) if seen_keys.contains(&key) => groups[group].duplicate_car_files.push(car_file.clone()),
+            Some(key) => seen_keys.push(key),
+            None => ()
+        }
+        groups[group].car_files.push(car_file);
+    }
+
+    groups.retain(|group| group.folders.len() > 1 || !group.duplicate_car_files.is_empty());
+    groups
+}
+
+/// Merges the group into the `keep` folder
+/// All car.jsons are repointed to it, the other folders and the duplicate car.jsons are deleted
+pub fn merge(group: &DuplicateGroup, keep: &str, state: &State) -> Result<()> {
+    if !group.folders.iter().any(|folder| folder == keep) {
+        return Err(Error::NotFound(format!("{} within this group", keep)));
+    }
+
+    for car_file in group.car_files.iter() {
+        if group.duplicate_car_files.contains(car_file) {
+            livery_ops::delete_car_file(car_file, state)?;
+            continue;
+        }
+
+        let car_json = livery_ops::get_car_file(car_file, state)?;
+        if livery_ops::read_car_for_livery_folder(&car_json).as_deref() != Some(keep) {
+            livery_ops::set_car_livery_folder(&car_json, keep, state)?;
+        }
+    }
+
+    for folder in group.folders.iter().filter(|folder| folder.as_str() != keep) {
+        livery_ops::delete_livery_folder(folder, state)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,316 @@
+use std::{path::{Path, PathBuf}, fs};
+
+use chrono::Local;
+
+use crate::{State, error::{Error, Result}};
+
+use super::{library::{self, LiveryFilter}, livery_ops::{self, Livery, ZipLiveryContent}, archive::ArchiveFormat, DATE_FORMAT_STR};
+
+/// Archives are named after the livery folder (or car.json) by default
+pub const DEFAULT_TEMPLATE: &str = "{name}";
+
+/// What to do if the archive to export into already exists
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IfExists {
+    /// Abort the export of this archive
+    Refuse,
+    /// Append -2, -3... to the name
+    Version,
+    /// Replace the existing archive
+    Overwrite
+}
+
+/// Which livery files are exported, the car.json is always included
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportProfile {
+    /// Only png and json files, ACC regenerates the dds files
+    Minimal,
+    /// Every file in the livery folder
+    Full
+}
+
+/// Selects the files of the livery folder that are exported
+/// The profile is applied first, then include (if set, only matching files are kept) and exclude patterns
+#[derive(Debug, Clone)]
+pub struct ExportFilter {
+    pub profile: ExportProfile,
+    /// Patterns with * and ?, matched against the file name
+    pub include: Vec<String>,
+    pub exclude: Vec<String>
+}
+
+impl ExportFilter {
+    pub fn matches(&self, file_name: &str) -> bool {
+        let lower = file_name.to_lowercase();
+        if self.profile == ExportProfile::Minimal && !lower.ends_with(".png") && !lower.ends_with(".json") {
+            return false;
+        }
+
+        if !self.include.is_empty() && !self.include.iter().any(|pattern| glob_match(pattern, file_name)) {
+            return false;
+        }
+
+        !self.exclude.iter().any(|pattern| glob_match(pattern, file_name))
+    }
+
+    /// Removes the livery files not matching the filter
+    pub fn apply(&self, livery: &mut Livery) {
+        livery.livery_files.retain(|item| self.matches(&item.name));
+    }
+}
+
+/// Where exported archives go and how they are named
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// Folder, or file path if only one archive is written, the current folder if None
+    pub output: Option<PathBuf>,
+    /// Naming template, see apply_template
+    pub template: String,
+    pub if_exists: IfExists
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions { output: None, template: DEFAULT_TEMPLATE.to_string(), if_exists: IfExists::Refuse }
+    }
+}
+
+impl ExportOptions {
+    /// The output is treated as file path, unless it is an existing folder or ends with a path separator
+    pub fn is_file_output(&self) -> bool {
+        match &self.output {
+            Some(output) => {
+                let text = output.to_string_lossy();
+                !output.is_dir() && !text.ends_with('/') && !text.ends_with('\\')
+            },
+            None => false
+        }
+    }
+
+    /// Path of the archive with this name (without extension), following the output and if_exists options
+    pub fn get_target(&self, name: &str, format: ArchiveFormat) -> Result<PathBuf> {
+        let extension = format!(".{}", format.get_extension());
+
+        let (folder, stem) = match &self.output {
+            Some(output) if self.is_file_output() => {
+                let file_name = output.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+                let stem = file_name.strip_suffix(extension.as_str()).unwrap_or(&file_name).to_string();
+                (output.parent().map(Path::to_path_buf).unwrap_or_default(), stem)
+            },
+            Some(output) => (output.clone(), sanitize_file_name(name)),
+            None => (PathBuf::new(), sanitize_file_name(name))
+        };
+
+        if !folder.as_os_str().is_empty() {
+            fs::create_dir_all(&folder)?;
+        }
+
+        let target = folder.join(format!("{}{}", stem, extension));
+        if !target.exists() {
+            return Ok(target);
+        }
+
+        match self.if_exists {
+            IfExists::Overwrite => Ok(target),
+            IfExists::Refuse => Err(Error::Conflict(format!("{} already exists", target.display()))),
+            IfExists::Version => {
+                let mut counter = 2;
+                loop {
+                    let target = folder.join(format!("{}-{}{}", stem, counter, extension));
+                    if !target.exists() {
+                        return Ok(target);
+                    }
+                    counter += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Fills the naming template with the fields of the car.json
+/// {name} livery folder (or car.json), {folder} livery folder, {team} team name, {number} race number,
+/// {car} car model, {date} today, fields without a value (e.g. no car.json) are left empty
+pub fn apply_template(template: &str, livery: &Livery) -> String {
+    let car = livery.car_json.as_ref().and_then(livery_ops::read_car_json);
+
+    template
+        .replace("{name}", &livery.get_name())
+        .replace("{folder}", &livery.livery_folder.clone().unwrap_or_default())
+        .replace("{team}", &car.as_ref().map(|car| car.team_name.clone().unwrap_or_default()).unwrap_or_default())
+        .replace("{number}", &car.as_ref().and_then(|car| car.race_number).map(|number| number.to_string()).unwrap_or_default())
+        .replace("{car}", &car.as_ref().map(|car| car.car_model_name()).unwrap_or_default())
+        .replace("{date}", &Local::now().format(DATE_FORMAT_STR).to_string())
+}
+
+/// Replaces characters that are not allowed in file names (on any platform)
+fn sanitize_file_name(name: &str) -> String {
+    let name: String = name.chars().map(|c| match c {
+        '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+        c if c.is_control() => '_',
+        c => c
+    }).collect();
+
+    let name = name.trim().trim_end_matches('.').to_string();
+    if name.is_empty() {
+        "export".to_string()
+    } else {
+        name
+    }
+}
+
+/// Finds the livery by car.json name or livery foldername
+/// For a livery folder the car.json pointing to it is included, unless only the livery is exported
+pub fn resolve(name: &String, only_livery: bool, state: &State) -> Result<Livery> {
+    if let Ok(car) = livery_ops::get_car_file(name, state) {
+        let (folder, content) = if let Some(folder) = livery_ops::read_car_for_livery_folder(&car) {
+            if let Ok(content) = livery_ops::get_livery_files(&folder, state) {
+                (Some(folder), content)
+            } else {
+                (None, Vec::<ZipLiveryContent>::new())
+            }
+        } else {
+            (None, Vec::<ZipLiveryContent>::new())
+        };
+
+        // Handling export flag
+        if !only_livery {
+            Ok(Livery {car_json: Some(car), livery_folder: folder, livery_files: content})
+        } else {
+            Ok(Livery {car_json: None, livery_folder: folder, livery_files: content})
+        }
+    } else if let Ok(content) = livery_ops::get_livery_files(name, state) {
+        //Challenge: we need to find the car_json that contains our folder
+
+        //Except when we don't:
+        if only_livery {
+            Ok(Livery {car_json: None, livery_folder: Some(name.clone()), livery_files: content})
+        } else {
+            let all_cars = livery_ops::get_all_car_json(state);
+
+            let mut car = None;
+
+            // We go through all car.json to find one which points to this folder
+            for item in all_cars {
+                if let Some(folder) = livery_ops::read_car_for_livery_folder(&item) {
+                    if &folder == name {
+                        car = Some(item);
+                        break;
+                    }
+                }
+            }
+
+            Ok(Livery {car_json: car, livery_folder: Some(name.clone()), livery_files: content })
+        }
+    } else {
+        Err(Error::NotFound(format!("car.json or livery folder {}", name)))
+    }
+}
+
+/// Matches a name against a pattern with * (any number of characters) and ? (one character), ignoring case
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    // Position in the pattern after the last *, and the position in the name it was matched against
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Letting the * consume one more character
+            p = star_p;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains('*') || pattern.contains('?')
+}
+
+/// Resolves names (or glob patterns) to the names of installed liveries
+/// A pattern matches car.json names (with or without .json) and livery folders, names are passed through as they are
+/// Returns the names and the patterns that matched nothing
+pub fn select_by_names(names: &[String], state: &State) -> (Vec<String>, Vec<String>) {
+    let installed = library::get_installed_liveries(state);
+    let mut output = Vec::<String>::new();
+    let mut unmatched = Vec::<String>::new();
+
+    for name in names {
+        if !is_glob(name) {
+            if !output.contains(name) {
+                output.push(name.clone());
+            }
+            continue;
+        }
+
+        let mut found = false;
+        for liver in installed.iter() {
+            let car_match = liver.car_file.as_ref().map(|car| glob_match(name, car) || glob_match(name, car.trim_end_matches(".json"))).unwrap_or(false);
+            let folder_match = liver.livery_folder.as_ref().map(|folder| glob_match(name, folder)).unwrap_or(false);
+
+            if car_match || folder_match {
+                found = true;
+                let liver_name = liver.car_file.clone().unwrap_or(liver.get_name());
+                if !output.contains(&liver_name) {
+                    output.push(liver_name);
+                }
+            }
+        }
+
+        if !found {
+            unmatched.push(name.clone());
+        }
+    }
+
+    (output, unmatched)
+}
+
+/// Names of all installed liveries passing the filter (all if the filter is empty)
+/// car.jsons are used as name where possible, so the livery is exported together with it
+pub fn select_by_filter(filter: &LiveryFilter, state: &State) -> Vec<String> {
+    library::get_installed_liveries(state).iter()
+        .filter(|liver| filter.matches(liver))
+        .map(|liver| liver.car_file.clone().unwrap_or(liver.get_name()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matches() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "#2_TeamIris_992"));
+        assert!(glob_match("#2_*", "#2_TeamIris_992"));
+        assert!(glob_match("*iris*", "#2_TeamIris_992"));
+        assert!(glob_match("#?_TeamIris_992", "#2_TeamIris_992"));
+        assert!(glob_match("*_*_992", "#2_TeamIris_992"));
+        assert!(glob_match("#2_teamiris_992", "#2_TeamIris_992"));
+
+        assert!(!glob_match("", "a"));
+        assert!(!glob_match("?", ""));
+        assert!(!glob_match("#3_*", "#2_TeamIris_992"));
+        assert!(!glob_match("*iris", "#2_TeamIris_992"));
+        assert!(!glob_match("#2_TeamIris_99?2", "#2_TeamIris_992"));
+    }
+
+    #[test]
+    fn globs() {
+        assert!(is_glob("#2_*"));
+        assert!(is_glob("car?"));
+        assert!(!is_glob("#2_TeamIris_992"));
+    }
+}
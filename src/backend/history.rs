@@ -0,0 +1,149 @@
+use std::{path::PathBuf, fs::{self, OpenOptions}, io::Write, fmt::Display};
+
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use serde::{Serialize, Deserialize};
+
+use crate::{State, error::Result};
+
+use super::{app_data::ACC_APP_FOLDER_NAME, DATE_FORMAT_STR};
+
+/// One entry per line, so recording never has to rewrite the file
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Operation {
+    Install,
+    Export,
+    Delete,
+    Rename,
+    Relink,
+    Merge,
+    Clean,
+    Undo,
+    LiveryMode
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Operation::Install => "install",
+            Operation::Export => "export",
+            Operation::Delete => "delete",
+            Operation::Rename => "rename",
+            Operation::Relink => "relink",
+            Operation::Merge => "merge",
+            Operation::Clean => "clean",
+            Operation::Undo => "undo",
+            Operation::LiveryMode => "livery-mode"
+        })
+    }
+}
+
+/// Something the tool changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub time: String,
+    pub operation: Operation,
+    /// Affected files and folders, as path within Customs
+    pub paths: Vec<String>,
+    /// The archive or folder installed from
+    pub source: Option<String>,
+    pub success: bool,
+    pub outcome: String
+}
+
+/// Filters applied when browsing the history
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    pub operation: Option<Operation>,
+    /// Has to be contained in one of the paths or the source
+    pub text: Option<String>,
+    pub since: Option<NaiveDate>,
+    pub failed_only: bool
+}
+
+fn get_time_format() -> String {
+    format!("{} %H:%M:%S", DATE_FORMAT_STR)
+}
+
+impl HistoryEntry {
+    /// `outcome` is the message of what happened, Err if the operation failed
+    pub fn new(operation: Operation, paths: Vec<String>, source: Option<String>, outcome: Result<String, String>) -> HistoryEntry {
+        let (success, outcome) = match outcome {
+            Ok(msg) => (true, msg),
+            Err(msg) => (false, msg)
+        };
+
+        HistoryEntry { time: Local::now().format(&get_time_format()).to_string(), operation, paths, source, success, outcome }
+    }
+
+    pub fn get_time(&self) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(&self.time, &get_time_format()).ok()
+    }
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        if let Some(operation) = self.operation {
+            if entry.operation != operation {
+                return false;
+            }
+        }
+
+        if self.failed_only && entry.success {
+            return false;
+        }
+
+        if let Some(since) = self.since {
+            match entry.get_time() {
+                Some(time) if time.date() >= since => (),
+                _ => return false
+            }
+        }
+
+        if let Some(text) = &self.text {
+            let text = text.to_lowercase();
+            let in_paths = entry.paths.iter().any(|path| path.to_lowercase().contains(text.as_str()));
+            let in_source = entry.source.as_ref().map(|source| source.to_lowercase().contains(text.as_str())).unwrap_or(false);
+            if !in_paths && !in_source {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Parses a date in the DATE_FORMAT_STR format, as used by --since
+pub fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date.trim(), DATE_FORMAT_STR).ok()
+}
+
+fn get_history_path(state: &State) -> PathBuf {
+    let mut path = state.root_folder.clone();
+    path.push(ACC_APP_FOLDER_NAME);
+    path.push(HISTORY_FILE);
+
+    path
+}
+
+/// Appends the entry to the history
+pub fn record(entry: &HistoryEntry, state: &State) -> Result<()> {
+    let path = get_history_path(state);
+    if let Some(folder) = path.parent() {
+        fs::create_dir_all(folder)?;
+    }
+
+    let line = serde_json::to_string(entry)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(writeln!(file, "{}", line)?)
+}
+
+/// Reads the history, oldest entry first, lines that can't be parsed are skipped
+pub fn get_history(state: &State) -> Vec<HistoryEntry> {
+    match fs::read_to_string(get_history_path(state)) {
+        Ok(data) => data.lines().filter_map(|line| serde_json::from_str(line).ok()).collect(),
+        Err(_) => Vec::new()
+    }
+}
@@ -0,0 +1,297 @@
+use std::path::{Path, PathBuf};
+
+use indicatif::ProgressBar;
+
+use crate::{State, error::{Error, Result}};
+
+use super::{library, livery_ops::{self, ArchiveContent, Livery, Conflict, ZipLiveryContent, RejectedEntry}, content_index::ContentIndex, transaction::Transaction, backup::JournalEntry};
+
+/// How to resolve a conflict without asking the user
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictPolicy {
+    /// Replace the installed files
+    Overwrite,
+    /// Keep the installed files and don't install this livery
+    Skip,
+    /// Install under a new, free name
+    Rename,
+    /// Overwrite if the files in the archive are newer than the installed ones, else skip
+    NewerWins,
+    /// Abort the install
+    Fail
+}
+
+/// Policies per conflict variant, None means the user is asked
+#[derive(Debug, Clone, Default)]
+pub struct ConflictPolicies {
+    pub both: Option<ConflictPolicy>,
+    pub car_only: Option<ConflictPolicy>,
+    pub livery_only: Option<ConflictPolicy>
+}
+
+impl ConflictPolicies {
+    pub fn get(&self, conflict: &Conflict) -> Option<ConflictPolicy> {
+        match conflict {
+            Conflict::Both => self.both,
+            Conflict::CarOnly => self.car_only,
+            Conflict::LiveryOnly => self.livery_only,
+            Conflict::None | Conflict::Identical => None
+        }
+    }
+
+    /// True if every conflict has a policy, so nobody has to be asked
+    pub fn is_complete(&self) -> bool {
+        self.both.is_some() && self.car_only.is_some() && self.livery_only.is_some()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstallOutcome {
+    Written,
+    Skipped,
+    /// Written under a new name
+    Renamed(String),
+    Identical,
+    /// Conflict policy was set to fail
+    Failed
+}
+
+/// Collects what happened to each livery of an install
+#[derive(Debug, Clone, Default)]
+pub struct InstallSummary {
+    pub written: Vec<String>,
+    pub skipped: Vec<String>,
+    pub renamed: Vec<(String, String)>,
+    pub identical: Vec<String>
+}
+
+impl InstallSummary {
+    pub fn add(&mut self, name: String, outcome: &InstallOutcome) {
+        match outcome {
+            InstallOutcome::Written => self.written.push(name),
+            InstallOutcome::Skipped | InstallOutcome::Failed => self.skipped.push(name),
+            InstallOutcome::Renamed(new_name) => self.renamed.push((name, new_name.clone())),
+            InstallOutcome::Identical => self.identical.push(name)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileStatus {
+    New,
+    Identical,
+    Overwritten
+}
+
+/// What writing a single file would do
+#[derive(Debug, Clone)]
+pub struct PlannedFile {
+    /// Path within the Customs folder
+    pub internal_path: String,
+    pub status: FileStatus,
+    pub size: u64,
+    /// Size of the file that is currently installed, 0 if there is none
+    pub installed_size: u64
+}
+
+/// What installing a livery would do, without touching the disk
+#[derive(Debug, Clone)]
+pub struct LiveryPlan {
+    pub name: String,
+    pub car_json: Option<String>,
+    /// Folder the car.json points to
+    pub livery_folder: Option<String>,
+    pub conflict: Conflict,
+    pub files: Vec<PlannedFile>
+}
+
+impl PlannedFile {
+    fn from(item: &ZipLiveryContent, state: &State, index: &mut ContentIndex) -> PlannedFile {
+        let target = item.get_target(state);
+        let installed_size = library::get_file_size(&target);
+
+        let status = if !target.exists() {
+            FileStatus::New
+        } else if item.is_same_as_target(state, index) {
+            FileStatus::Identical
+        } else {
+            FileStatus::Overwritten
+        };
+
+        PlannedFile { internal_path: item.get_interal_path(), status, size: item.get_size(), installed_size }
+    }
+
+    /// Change in size on disk, negative if the file shrinks
+    pub fn size_delta(&self) -> i64 {
+        self.size as i64 - self.installed_size as i64
+    }
+}
+
+/// Creates the install plan for a livery
+pub fn plan(item: &Livery, state: &State, index: &mut ContentIndex) -> LiveryPlan {
+    let files = item.car_json.iter().chain(item.livery_files.iter()).map(|file| PlannedFile::from(file, state, index)).collect();
+
+    LiveryPlan {
+        name: item.get_name(),
+        car_json: item.car_json.as_ref().map(|car| car.name.clone()),
+        livery_folder: item.car_json.as_ref().and_then(super::livery_ops::read_car_for_livery_folder),
+        conflict: item.check_if_conflict(state, index),
+        files
+    }
+}
+
+/// Finds a car.json name that is not in use yet, by appending -1, -2...
+pub fn get_free_car_name(name: &str, state: &State) -> String {
+    let mut stem = PathBuf::from(name);
+    stem.set_extension("");
+    let stem = stem.to_string_lossy().to_string();
+
+    let mut counter = 1;
+    loop {
+        let candidate = format!("{}-{}.json", stem, counter);
+        let mut target = library::get_cars_folder(state);
+        target.push(&candidate);
+
+        if !target.exists() {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+/// Finds a livery folder name that is not in use yet, by appending -1, -2...
+pub fn get_free_livery_folder(folder: &str, state: &State) -> String {
+    let mut counter = 1;
+    loop {
+        let candidate = format!("{}-{}", folder, counter);
+        let mut target = library::get_liveries_folder(state);
+        target.push(&candidate);
+
+        if !target.exists() {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+/// Checks the conflicts of all liveries before anything is written
+/// Err for the first livery whose conflict is set to fail the install
+pub fn check_fail_policy(results: &[Livery], policies: &ConflictPolicies, state: &State, index: &mut ContentIndex) -> Result<()> {
+    for item in results {
+        if policies.get(&item.check_if_conflict(state, index)) == Some(ConflictPolicy::Fail) {
+            return Err(Error::Conflict(format!("Conflict on {}, aborting install", item.get_name())));
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves the conflict of a livery based on the policy and writes it if needed
+pub fn apply_policy(mut item: Livery, conflict: &Conflict, policy: ConflictPolicy, state: &State, transaction: &mut Transaction) -> Result<InstallOutcome> {
+    match policy {
+        ConflictPolicy::Overwrite => {
+            item.write(state, transaction)?;
+            Ok(InstallOutcome::Written)
+        },
+        ConflictPolicy::Skip => Ok(InstallOutcome::Skipped),
+        ConflictPolicy::Fail => Ok(InstallOutcome::Failed),
+        ConflictPolicy::NewerWins => {
+            let newer = match (item.get_modified(), item.get_installed_modified(state)) {
+                (Some(incoming), Some(installed)) => incoming > installed,
+                (Some(_), None) => true,
+                _ => false
+            };
+
+            if newer {
+                item.write(state, transaction)?;
+                Ok(InstallOutcome::Written)
+            } else {
+                Ok(InstallOutcome::Skipped)
+            }
+        },
+        ConflictPolicy::Rename => {
+            let mut new_names = Vec::<String>::new();
+
+            if matches!(conflict, Conflict::CarOnly | Conflict::Both) {
+                if let Some(car) = &item.car_json {
+                    let name = get_free_car_name(&car.name, state);
+                    item.rename_car_json(&name);
+                    new_names.push(name);
+                }
+            }
+
+            if matches!(conflict, Conflict::LiveryOnly | Conflict::Both) {
+                if let Some(folder) = item.livery_folder.clone() {
+                    let folder = get_free_livery_folder(&folder, state);
+                    item.rename_livery_folder(&folder)?;
+                    new_names.push(folder);
+                }
+            }
+
+            item.write(state, transaction)?;
+            Ok(InstallOutcome::Renamed(new_names.join(", ")))
+        }
+    }
+}
+
+/// Result of an install without user interaction
+#[derive(Debug, Clone, Default)]
+pub struct InstallReport {
+    pub summary: InstallSummary,
+    /// Entries of the archive that were not installed, see livery_ops::get_import_content
+    pub rejected: Vec<RejectedEntry>,
+    /// None if no file was written
+    pub journal: Option<JournalEntry>
+}
+
+/// Installs an archive or folder without asking, conflicts without a policy are skipped
+/// Everything is rolled back if a livery fails to write or a policy is set to fail
+/// Problems found by verifying the archive are ignored and no history is written, see install_content and history::record
+/// The progressbar is advanced while reading the archive, pass ProgressBar::hidden() to show nothing
+pub fn install(path: &Path, policies: &ConflictPolicies, state: &State, progressbar: &ProgressBar) -> Result<InstallReport> {
+    let content = livery_ops::get_import_content(&path.to_path_buf(), state, progressbar)?;
+    install_content(content, &path.to_string_lossy(), policies, state)
+}
+
+/// Installs content already read by livery_ops::get_import_content, e.g. after checking content.verify
+/// source is recorded in the journal
+pub fn install_content(content: ArchiveContent, source: &str, policies: &ConflictPolicies, state: &State) -> Result<InstallReport> {
+    let results = livery_ops::group_content(content.files, content.manifest.as_ref());
+
+    let mut index = ContentIndex::load(state);
+    check_fail_policy(&results, policies, state, &mut index)?;
+
+    let mut transaction = Transaction::begin(state)?;
+    let mut summary = InstallSummary::default();
+
+    for item in results {
+        let name = item.get_name();
+        let conflict = item.check_if_conflict(state, &mut index);
+
+        let res = match (&conflict, policies.get(&conflict)) {
+            (Conflict::None, _) => item.write(state, &mut transaction).map(|_| InstallOutcome::Written),
+            (Conflict::Identical, _) => Ok(InstallOutcome::Identical),
+            (_, Some(policy)) => apply_policy(item, &conflict, policy, state, &mut transaction),
+            (_, None) => Ok(InstallOutcome::Skipped)
+        };
+
+        let error = match res {
+            Ok(InstallOutcome::Failed) => Error::Conflict(format!("Conflict on {}, aborting install", name)),
+            Ok(outcome) => {
+                summary.add(name, &outcome);
+                continue;
+            },
+            Err(e) => e
+        };
+
+        // Nothing was moved into place yet, so this only removes the staged files
+        let _ = transaction.rollback();
+        return Err(error);
+    }
+
+    let journal = transaction.commit(source, state)?;
+    index.prune(state);
+    index.save(state)?;
+
+    Ok(InstallReport { summary, rejected: content.rejected, journal })
+}
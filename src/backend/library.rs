@@ -0,0 +1,187 @@
+use std::{path::{PathBuf, Path}, fs};
+
+use serde::Serialize;
+
+use crate::{State, model::CarJson};
+
+use super::livery_ops::{self, ACC_CUSTOMS_FOLDER_NAME, ACC_LIVERY_FOLDER_NAME, ACC_CAR_FOLDER_NAME};
+
+/// An installed livery, as in a car.json and/or the livery folder it points to
+#[derive(Debug, Clone)]
+pub struct InstalledLivery {
+    pub car_file: Option<String>,
+    pub car: Option<CarJson>,
+    pub livery_folder: Option<String>,
+    pub folder_exists: bool,
+    pub file_count: usize,
+    pub total_size: u64
+}
+
+/// Flat version of InstalledLivery, used for the list output
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveryListEntry {
+    pub file_name: Option<String>,
+    pub team_name: Option<String>,
+    pub race_number: Option<i32>,
+    pub car_model: Option<String>,
+    pub car_model_type: Option<i32>,
+    pub livery_folder: Option<String>,
+    pub file_count: usize,
+    pub total_size: u64
+}
+
+/// Filters applied when listing (and selecting) installed liveries
+#[derive(Debug, Clone, Default)]
+pub struct LiveryFilter {
+    pub car_model: Option<String>,
+    pub team: Option<String>
+}
+
+impl InstalledLivery {
+    pub fn to_list_entry(&self) -> LiveryListEntry {
+        LiveryListEntry {
+            file_name: self.car_file.clone(),
+            team_name: self.car.as_ref().and_then(|car| car.team_name.clone()),
+            race_number: self.car.as_ref().and_then(|car| car.race_number),
+            car_model: self.car.as_ref().map(|car| car.car_model_name()),
+            car_model_type: self.car.as_ref().and_then(|car| car.car_model_type),
+            livery_folder: self.livery_folder.clone(),
+            file_count: self.file_count,
+            total_size: self.total_size
+        }
+    }
+
+    /// Name used to refer to this livery, the livery folder if it has one, else the car.json
+    pub fn get_name(&self) -> String {
+        if let Some(folder) = &self.livery_folder {
+            folder.clone()
+        } else if let Some(car) = &self.car_file {
+            car.clone()
+        } else {
+            String::new()
+        }
+    }
+}
+
+impl LiveryFilter {
+    pub fn is_empty(&self) -> bool {
+        self.car_model.is_none() && self.team.is_none()
+    }
+
+    /// Liveries without a (parsable) car.json only pass if no filter is set
+    pub fn matches(&self, livery: &InstalledLivery) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+
+        let car = if let Some(car) = &livery.car {
+            car
+        } else {
+            return false;
+        };
+
+        if let Some(model) = &self.car_model {
+            let by_id = model.trim().parse::<i32>().map(|id| Some(id) == car.car_model_type).unwrap_or(false);
+            if !by_id && !car.car_model_name().to_lowercase().contains(model.to_lowercase().as_str()) {
+                return false;
+            }
+        }
+
+        if let Some(team) = &self.team {
+            if !car.team_name.clone().unwrap_or_default().to_lowercase().contains(team.to_lowercase().as_str()) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+pub fn get_cars_folder(state: &State) -> PathBuf {
+    let mut folder = state.root_folder.clone();
+    folder.push(ACC_CUSTOMS_FOLDER_NAME);
+    folder.push(ACC_CAR_FOLDER_NAME);
+
+    folder
+}
+
+pub fn get_liveries_folder(state: &State) -> PathBuf {
+    let mut folder = state.root_folder.clone();
+    folder.push(ACC_CUSTOMS_FOLDER_NAME);
+    folder.push(ACC_LIVERY_FOLDER_NAME);
+
+    folder
+}
+
+/// Returns the names of all folders within the livery folder of ACC
+pub fn get_all_livery_folders(state: &State) -> Vec<String> {
+    let mut output = Vec::<String>::new();
+
+    if let Ok(folder_content) = get_liveries_folder(state).read_dir() {
+        for item in folder_content.flatten() {
+            if item.path().is_dir() {
+                output.push(item.file_name().to_string_lossy().to_string());
+            }
+        }
+    }
+
+    output.sort();
+    output
+}
+
+/// Counts the files and their combined size within a livery folder, without reading them
+pub fn get_livery_folder_stats(livery: &str, state: &State) -> Option<(usize, u64)> {
+    let mut folder = get_liveries_folder(state);
+    folder.push(livery);
+
+    let mut count = 0;
+    let mut size = 0;
+
+    for item in folder.read_dir().ok()?.flatten() {
+        if let Ok(meta) = item.metadata() {
+            if meta.is_file() {
+                count += 1;
+                size += meta.len();
+            }
+        }
+    }
+
+    Some((count, size))
+}
+
+/// Pairs every car.json with its livery folder, folders that no car.json references are added on their own
+pub fn get_installed_liveries(state: &State) -> Vec<InstalledLivery> {
+    let mut output = Vec::<InstalledLivery>::new();
+    let mut folders = get_all_livery_folders(state);
+
+    let mut cars = livery_ops::get_all_car_json(state);
+    cars.sort_by_key(|car| car.name.to_lowercase());
+
+    for item in cars {
+        let car = livery_ops::read_car_json(&item);
+        let livery_folder = car.as_ref().and_then(|car| car.livery_folder());
+
+        let (folder_exists, file_count, total_size) = match livery_folder.as_ref().and_then(|folder| get_livery_folder_stats(folder, state)) {
+            Some((count, size)) => (true, count, size),
+            None => (false, 0, 0)
+        };
+
+        if let Some(folder) = &livery_folder {
+            folders.retain(|name| name != folder);
+        }
+
+        output.push(InstalledLivery { car_file: Some(item.name), car, livery_folder, folder_exists, file_count, total_size });
+    }
+
+    for folder in folders {
+        let (file_count, total_size) = get_livery_folder_stats(&folder, state).unwrap_or((0, 0));
+        output.push(InstalledLivery { car_file: None, car: None, livery_folder: Some(folder), folder_exists: true, file_count, total_size });
+    }
+
+    output
+}
+
+/// Size of a file, 0 if it doesn't exist
+pub fn get_file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|meta| meta.len()).unwrap_or(0)
+}
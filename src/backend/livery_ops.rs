@@ -1,10 +1,11 @@
-use std::{path::PathBuf, fs::{self, File}, io::{Cursor, self, Write}};
+use std::{path::{PathBuf, Path}, fs::{self, File}, io::{self, BufReader}, collections::{HashSet, HashMap}};
 
-use indicatif::{ProgressBar, ProgressStyle};
+use chrono::NaiveDateTime;
+use indicatif::ProgressBar;
 
-use crate::State;
+use crate::{State, model::CarJson, error::{Error, Result}};
 
-use super::{SafeRead, get_filename};
+use super::{get_filename, archive::{self, ArchiveFormat, ArchiveWriter, CompressionOptions}, content_index::{self, ContentIndex}, transaction::Transaction, manifest::{self, PackManifest}, verify::{Verifier, VerifyReport}};
 
 pub const ACC_TEMP_FOLDER:&str = "temp";
 
@@ -12,19 +13,16 @@ pub const ACC_CUSTOMS_FOLDER_NAME: &str = "Customs";
 pub const ACC_CAR_FOLDER_NAME: &str = "Cars";
 pub const ACC_LIVERY_FOLDER_NAME: &str = "Liveries";
 
-pub fn get_temp_folder(state: &State) -> Option<PathBuf> {
+/// Returns the temp folder, creating it if necessary
+pub fn get_temp_folder(state: &State) -> Result<PathBuf> {
     let mut folder = state.root_folder.clone();
 
     folder.push(ACC_TEMP_FOLDER);
-    if folder.exists() {
-        return None;
+    if !folder.is_dir() {
+        fs::create_dir_all(folder.as_path())?;
     }
 
-    if fs::create_dir_all(folder.as_path()).is_ok() {
-        return Some(folder);
-    }
-
-    None
+    Ok(folder)
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -43,7 +41,7 @@ impl ToString for CustomFolder {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Conflict {
     None,
     CarOnly,
@@ -56,7 +54,10 @@ pub enum Conflict {
 pub struct ZipLiveryContent {
     pub upper: CustomFolder,
     pub name: String,
-    pub file: Vec<u8>
+    pub file: Vec<u8>,
+    pub modified: Option<NaiveDateTime>,
+    /// If set the content is not held in `file`, but read from this path when needed
+    pub source: Option<PathBuf>
 }
 
 impl ZipLiveryContent {
@@ -70,25 +71,70 @@ impl ZipLiveryContent {
         file
     }
 
-    pub fn is_same_as_target(& self, state: &State) -> bool {
-        if let Ok(content) = fs::read(self.get_target(state)) {
-            if content.len() != self.file.len() {
-                return false;
-            }
+    /// Size of the content, without reading it
+    pub fn get_size(& self) -> u64 {
+        match &self.source {
+            Some(source) => fs::metadata(source).map(|meta| meta.len()).unwrap_or(0),
+            None => self.file.len() as u64
+        }
+    }
+
+    /// Writes the content to the target, files on disk are copied without loading them into memory
+    pub fn write_to(& self, target: &Path) -> Result<()> {
+        match &self.source {
+            Some(source) => fs::copy(source, target).map(|_| ())?,
+            None => fs::write(target, self.file.as_slice())?
+        }
+
+        Ok(())
+    }
+
+    /// Compares the content with the installed file
+    /// The installed file is looked up in the content index, so only the incoming file has to be hashed
+    pub fn is_same_as_target(& self, state: &State, index: &mut ContentIndex) -> bool {
+        let target = self.get_target(state);
 
-            // Why run a hash function if we have one in memory already, and have to load the other into memory to hash anyway
-            // We can just iterate over all bytes
+        // Different sizes can't be the same, no need to hash anything
+        if !target.is_file() || super::library::get_file_size(&target) != self.get_size() {
+            return false;
+        }
+
+        let installed = match index.get_hash(&self.get_interal_path(), state) {
+            Some(hash) => hash,
+            None => return false
+        };
+
+        let incoming = match &self.source {
+            Some(source) => content_index::hash_file(source).ok(),
+            None => Some(content_index::hash_bytes(self.file.as_slice()))
+        };
+
+        incoming == Some(installed)
+    }
+
+    /// Verifies the file stays within Customs/Cars or Customs/Liveries when written
+    /// For car.jsons the livery folder they point to is checked too
+    pub fn check_safe(& self) -> Result<(), String> {
+        if !is_safe_name(&self.name) {
+            return Err(format!("invalid file name {}", self.name));
+        }
 
-            for (base, target) in self.file.iter().zip(content.iter()) {
-                if base != target {
-                    return false;
+        match &self.upper {
+            CustomFolder::Liveries(folder) => {
+                if !is_safe_name(folder) {
+                    return Err(format!("invalid livery folder {}", folder));
+                }
+            },
+            CustomFolder::Cars => {
+                if let Some(folder) = read_car_for_livery_folder(self) {
+                    if !is_safe_name(&folder) {
+                        return Err(format!("customSkinName {} points outside of the livery folder", folder));
+                    }
                 }
             }
-
-            return true;
         }
 
-        false
+        Ok(())
     }
 
     pub fn get_interal_path(& self) -> String {
@@ -104,14 +150,14 @@ pub struct Livery {
 }
 
 impl Livery {
-    pub fn check_if_conflict(& self, state: &State) -> Conflict {
+    pub fn check_if_conflict(& self, state: &State, index: &mut ContentIndex) -> Conflict {
         let mut conflict = Conflict::None;
         let mut car_json_conflict = false;
 
         if let Some(car) = &self.car_json {
             if car.get_target(state).exists() {
                 car_json_conflict = true;
-                if car.is_same_as_target(state) {
+                if car.is_same_as_target(state, index) {
                     conflict = Conflict::Identical;
                 } else {
                     conflict = Conflict::CarOnly;
@@ -133,7 +179,7 @@ impl Livery {
                             if item.get_target(state).exists() {
                                 conflict = match conflict {
                                     Conflict::None => {
-                                        if item.is_same_as_target(state) {
+                                        if item.is_same_as_target(state, index) {
                                             Conflict::Identical 
                                         } else {
                                             return Conflict::LiveryOnly;
@@ -143,7 +189,7 @@ impl Livery {
                                         return Conflict::Both;
                                     },
                                     Conflict::Identical => {
-                                        if item.is_same_as_target(state) {
+                                        if item.is_same_as_target(state, index) {
                                             Conflict::Identical 
                                         } else if car_json_conflict {
                                             return Conflict::Both;
@@ -175,23 +221,66 @@ impl Livery {
         Some(folder)
     }
 
-    pub fn write(&self, state: &State) -> io::Result<()> {
-        // Setting up car.json
-        if let Some(car) = &self.car_json {
-            fs::write(car.get_target(state), car.file.clone())?;
+    /// Changes the file name of the car.json, the content stays the same
+    pub fn rename_car_json(&mut self, name: &str) {
+        if let Some(car) = self.car_json.as_mut() {
+            let mut file_name = PathBuf::from(name);
+            file_name.set_extension("json");
+            car.name = get_filename(&file_name);
         }
+    }
 
-        // Creating the folder if necessary
-        if let Some(folder) = self.get_livery_folder(state) {
-            if !folder.exists() {
-                fs::create_dir_all(folder)?;
+    /// Moves the livery files into a different folder and points the car.json to it
+    pub fn rename_livery_folder(&mut self, folder: &str) -> Result<()> {
+        if let Some(car) = self.car_json.as_mut() {
+            let mut parsed = read_car_json(car).ok_or_else(|| Error::Json(format!("{} is not a valid car.json", car.name)))?;
+            parsed.set_livery_folder(folder);
+            car.file = parsed.to_bytes().ok_or_else(|| Error::Json(format!("failed to serialize {}", car.name)))?;
+        }
+
+        for item in self.livery_files.iter_mut() {
+            item.upper = CustomFolder::Liveries(folder.to_string());
+        }
+        self.livery_folder = Some(folder.to_string());
+
+        Ok(())
+    }
+
+    /// Newest modified time of all files in this livery
+    pub fn get_modified(&self) -> Option<NaiveDateTime> {
+        self.car_json.iter().chain(self.livery_files.iter()).filter_map(|item| item.modified).max()
+    }
+
+    /// Newest modified time of the already installed files this livery would replace
+    pub fn get_installed_modified(&self, state: &State) -> Option<NaiveDateTime> {
+        self.car_json.iter().chain(self.livery_files.iter()).filter_map(|item| super::get_modified(&item.get_target(state))).max()
+    }
+
+    pub fn get_name(&self) -> String {
+        if let Some(liver) = self.livery_folder.clone() {
+            liver
+        } else if let Some(car) = &self.car_json {
+            car.name.clone()
+        } else {
+            String::new()
+        }
+    }
+
+    /// Stages the livery as part of the transaction, it is moved into place when the transaction is committed
+    pub fn write(&self, state: &State, transaction: &mut Transaction) -> Result<()> {
+        // Refusing to write anything if a single file would end up outside of Customs
+        if let Some(folder) = &self.livery_folder {
+            if !is_safe_name(folder) {
+                return Err(Error::InvalidArchive(format!("invalid livery folder {}", folder)));
             }
         }
+        for item in self.car_json.iter().chain(self.livery_files.iter()) {
+            item.check_safe().map_err(Error::InvalidArchive)?;
+        }
 
-        // Writing the livery files
-        let iter = self.livery_files.iter();
-        for item in iter {
-            fs::write(item.get_target(state), item.file.clone())?;
+        // Staging car.json and livery files, folders are created when they are moved into place
+        for item in self.car_json.iter().chain(self.livery_files.iter()) {
+            transaction.stage(item, item.get_target(state))?;
         }
 
         Ok(())
@@ -199,23 +288,24 @@ impl Livery {
 }
 
 /// Finds and read a specific car.json within the cars folder of ACC (aka one that is already installed)
-pub fn get_car_file(car: &String, state: &State) -> Option<ZipLiveryContent> {
+pub fn get_car_file(car: &String, state: &State) -> Result<ZipLiveryContent> {
     let mut name = PathBuf::from(car);
     name.set_extension("json");
     let name = get_filename(&name);
 
-    let file = ZipLiveryContent { upper: CustomFolder::Cars, name, file: Vec::<u8>::new()};
-    if file.get_target(state).exists() {
-        if let Ok(content) = fs::read(file.get_target(state)) {
-            return Some(ZipLiveryContent { upper: file.upper, name: file.name, file: content});
-        }
+    let file = ZipLiveryContent { upper: CustomFolder::Cars, name, file: Vec::<u8>::new(), modified: None, source: None };
+    if !file.get_target(state).exists() {
+        return Err(Error::NotFound(format!("car.json {}", file.name)));
     }
 
-    None
+    let content = fs::read(file.get_target(state))?;
+    let modified = super::get_modified(&file.get_target(state));
+    Ok(ZipLiveryContent { upper: file.upper, name: file.name, file: content, modified, source: None })
 }
 
-/// Read all files out of a specific folder within the livery folder of ACC (aka one that is already installed)
-pub fn get_livery_files(livery: &String, state: &State) -> Option<Vec<ZipLiveryContent>> {
+/// Lists all files of a specific folder within the livery folder of ACC (aka one that is already installed)
+/// The files are not loaded into memory, their `source` points to the installed file
+pub fn get_livery_files(livery: &String, state: &State) -> Result<Vec<ZipLiveryContent>> {
     let mut folder = state.root_folder.clone();
 
     folder.push(ACC_CUSTOMS_FOLDER_NAME);
@@ -223,20 +313,20 @@ pub fn get_livery_files(livery: &String, state: &State) -> Option<Vec<ZipLiveryC
     folder.push(&livery);
 
     if !folder.exists() || folder.is_file() {
-        return None;
+        return Err(Error::NotFound(format!("livery folder {}", livery)));
     }
 
     let mut output = Vec::<ZipLiveryContent>::new();
 
     if let Ok(mut folder_content) = folder.read_dir() {
         while let Some(Ok(item)) = folder_content.next() {
-            if let Ok(content) = fs::read(item.path()) {
-                output.push(ZipLiveryContent { upper: CustomFolder::Liveries(livery.clone()), name: item.file_name().to_str().expect("it is a string").to_string(), file: content});
+            if item.path().is_file() {
+                output.push(ZipLiveryContent { upper: CustomFolder::Liveries(livery.clone()), name: item.file_name().to_string_lossy().to_string(), file: Vec::<u8>::new(), modified: super::get_modified(&item.path()), source: Some(item.path()) });
             }
         }  
     }
 
-    return Some(output);
+    Ok(output)
 }
 
 /// Returns all car.json from the cars folder of ACC (aka all currently installed)
@@ -251,7 +341,7 @@ pub fn get_all_car_json(state: &State) -> Vec<ZipLiveryContent> {
     if let Ok(mut folder_content) = folder.read_dir() {
         while let Some(Ok(item)) = folder_content.next() {
             if let Ok(content) = fs::read(item.path()) {
-                output.push(ZipLiveryContent { upper: CustomFolder::Cars, name: item.file_name().to_str().expect("it is a string").to_string(), file: content});
+                output.push(ZipLiveryContent { upper: CustomFolder::Cars, name: item.file_name().to_string_lossy().to_string(), file: content, modified: super::get_modified(&item.path()), source: None });
             }
         }  
     }
@@ -259,72 +349,432 @@ pub fn get_all_car_json(state: &State) -> Vec<ZipLiveryContent> {
     output
 }
 
-/// Reads a zip file and parses it into an unsorted list of ZipLiveryContent files
-/// Run group_up to sort the data
-pub fn get_zip_content(zip_file: &PathBuf) -> Option<Vec<ZipLiveryContent>> {
-    if let Ok(stream) = fs::read(&zip_file) {
-        let stream = Cursor::new(stream);
-
-        let mut content = Vec::<ZipLiveryContent>::new();
-        // Reading the zip
-        if let Ok(zip_content) = zip::ZipArchive::new(&mut stream.clone()) {
-            let iter = zip_content.file_names();
-            let progressbar = ProgressBar::new(zip_content.len() as u64);
-            progressbar.set_style(ProgressStyle::with_template("[{elapsed_precise}] {bar:50.cyan/blue} {pos:>1}/{len:5}")
-                    .expect("Progress Style is valid (At least when it was typed, an update to indicatif might have broken it)")
-                    .progress_chars("##-"));
-
-            // Reading the file
-            for item in iter {
-                let mut data = Vec::<u8>::new();
-                let mut internal_path = PathBuf::from(item);
-                if zip_extensions::read::zip_extract_file_to_memory(zip_file, &internal_path, &mut data).is_ok() {
-                    
-                    // 
-                    let name = super::get_filename(&internal_path);
-                    let upper = if internal_path.pop() && internal_path.file_name().is_some() {
-                        let folder_name = super::get_filename(&internal_path).trim().to_string();
-
-                        match folder_name.to_lowercase().as_str() {
-                            "cars" => CustomFolder::Cars,
-                            _ => CustomFolder::Liveries(folder_name)
-                        }
-                    } else {
-                        let mut origin_file = zip_file.clone();
-                        origin_file.set_extension("");
-                        CustomFolder::Liveries(super::get_filename(&origin_file))
-                    };
-
-                    
-                    content.push(ZipLiveryContent { upper, name, file: data });
+/// Points an installed car.json to a different livery folder, all other fields are kept as they are
+pub fn set_car_livery_folder(car_json: &ZipLiveryContent, livery_folder: &str, state: &State) -> Result<()> {
+    let mut car = read_car_json(car_json).ok_or_else(|| Error::Json(format!("{} is not a valid car.json", car_json.name)))?;
+    car.set_livery_folder(livery_folder);
+
+    let content = car.to_bytes().ok_or_else(|| Error::Json(format!("failed to serialize {}", car_json.name)))?;
+    Ok(fs::write(car_json.get_target(state), content)?)
+}
+
+/// Deletes an installed car.json
+pub fn delete_car_file(car: &String, state: &State) -> Result<()> {
+    let mut name = PathBuf::from(car);
+    name.set_extension("json");
+
+    let file = ZipLiveryContent { upper: CustomFolder::Cars, name: get_filename(&name), file: Vec::<u8>::new(), modified: None, source: None };
+    Ok(fs::remove_file(file.get_target(state))?)
+}
+
+/// Deletes an installed livery folder with all its files
+pub fn delete_livery_folder(livery: &String, state: &State) -> Result<()> {
+    let liver = Livery { livery_folder: Some(livery.clone()), car_json: None, livery_files: Vec::<ZipLiveryContent>::new() };
+
+    match liver.get_livery_folder(state) {
+        Some(folder) if folder.is_dir() => Ok(fs::remove_dir_all(folder)?),
+        _ => Err(Error::NotFound(format!("livery folder {}", livery)))
+    }
+}
+
+/// Entry of an archive that was not imported
+#[derive(Debug, Clone)]
+pub struct RejectedEntry {
+    pub entry: String,
+    pub reason: String
+}
+
+/// Files read from an archive, plus the entries that were refused
+#[derive(Debug, Default)]
+pub struct ArchiveContent {
+    pub files: Vec<ZipLiveryContent>,
+    pub rejected: Vec<RejectedEntry>,
+    /// The manifest, if the archive was exported by this tool
+    pub manifest: Option<PackManifest>,
+    /// Holds the extracted files, they are deleted when this is dropped
+    pub staging: Option<StagingFolder>,
+    /// Problems found while reading the files, see verify::verify
+    pub verify: VerifyReport,
+    /// SHA-256 of the read files by their source, so the manifest check doesn't read them again
+    hashes: HashMap<PathBuf, String>
+}
+
+/// Checks that a name can be used as a single file or folder name within Customs without escaping it
+pub fn is_safe_name(name: &str) -> bool {
+    !name.trim().is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', ':', '\0'])
+}
+
+/// Splits the name of an archive entry into its path components
+/// Returns None for directories, and Err for anything that could resolve outside of the target folder
+pub fn normalize_entry_name(entry: &str) -> Result<Option<Vec<String>>, String> {
+    if entry.ends_with(['/', '\\']) {
+        return Ok(None);
+    }
+
+    if entry.starts_with(['/', '\\']) {
+        return Err("absolute path".to_string());
+    }
+
+    let mut components = Vec::<String>::new();
+    for part in entry.split(['/', '\\']) {
+        match part {
+            "" | "." => (),
+            ".." => return Err("path traversal (..)".to_string()),
+            _ if part.contains([':', '\0']) => return Err("drive prefix or invalid character".to_string()),
+            _ => components.push(part.to_string())
+        }
+    }
+
+    if components.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(components))
+}
+
+/// Livery folder for files at the root of an imported folder without a name (e.g. `/`)
+pub const DEFAULT_FOLDER_NAME: &str = "Imported";
+
+/// How deep archives within archives are opened
+pub const MAX_ARCHIVE_DEPTH: usize = 4;
+
+/// Removes folders that wrap the whole archive (e.g. MyPack/...), as long as they are not Cars or Liveries
+/// Returns the name of the innermost removed folder, as files at the root belong to a livery of that name
+pub fn strip_wrapping_folders(entries: &mut [Vec<String>]) -> Option<String> {
+    let mut wrapper = None;
+
+    loop {
+        let first = entries.first()?.first()?.clone();
+        if is_layout_marker(&first) || entries.iter().any(|entry| entry.len() < 2 || entry[0] != first) {
+            return wrapper;
+        }
+
+        for entry in entries.iter_mut() {
+            entry.remove(0);
+        }
+        wrapper = Some(first);
+    }
+}
+
+fn is_layout_marker(folder: &str) -> bool {
+    matches!(folder.to_lowercase().as_str(), "cars" | "liveries")
+}
+
+/// Decides where an entry belongs, based on the last Cars or Liveries folder within the path
+/// If neither exists, the first folder is the livery folder, and files at the root go into `default_folder`
+/// The bool is true if the layout was detected via a Cars/Liveries folder
+pub fn get_entry_folder(components: &[String], default_folder: &str) -> (CustomFolder, bool) {
+    let folders = &components[..components.len() - 1];
+
+    if let Some(index) = folders.iter().rposition(|folder| is_layout_marker(folder)) {
+        if folders[index].to_lowercase() == "cars" {
+            return (CustomFolder::Cars, true);
+        }
+
+        return match folders.get(index + 1) {
+            Some(folder) => (CustomFolder::Liveries(folder.trim().to_string()), true),
+            None => (CustomFolder::Liveries(default_folder.to_string()), true)
+        };
+    }
+
+    match folders.first() {
+        Some(folder) => (CustomFolder::Liveries(folder.trim().to_string()), false),
+        None => (CustomFolder::Liveries(default_folder.to_string()), false)
+    }
+}
+
+/// A file of an archive or folder, before it is sorted into Cars/Liveries
+struct RawEntry {
+    entry: String,
+    components: Vec<String>,
+    source: PathBuf,
+    modified: Option<NaiveDateTime>
+}
+
+/// Folder within the temp folder that archives are extracted into, it is deleted again when dropped
+#[derive(Debug)]
+pub struct StagingFolder {
+    path: PathBuf,
+    counter: usize
+}
+
+impl StagingFolder {
+    pub fn create(state: &State) -> Result<StagingFolder> {
+        let mut path = get_temp_folder(state)?;
+        path.push(format!("import-{}-{}", std::process::id(), chrono::Local::now().format("%Y%m%d%H%M%S%f")));
+
+        fs::create_dir_all(&path)?;
+
+        Ok(StagingFolder { path, counter: 0 })
+    }
+
+    pub fn get_path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Returns a path for the next file, files are simply numbered to avoid any issues with their names
+    fn next_file(&mut self) -> PathBuf {
+        self.counter += 1;
+
+        let mut file = self.path.clone();
+        file.push(self.counter.to_string());
+        file
+    }
+}
+
+impl Drop for StagingFolder {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Reads an archive or a folder, see get_zip_content and get_folder_content
+/// Run group_content to sort the data, the ArchiveContent has to be kept until the files are written, as it owns the staging folder
+/// The progressbar is advanced per archive entry, pass ProgressBar::hidden() to show nothing
+pub fn get_import_content(path: &PathBuf, state: &State, progressbar: &ProgressBar) -> Result<ArchiveContent> {
+    if !path.exists() {
+        return Err(Error::NotFound(path.display().to_string()));
+    }
+
+    let mut staging = StagingFolder::create(state)?;
+
+    let mut content = if path.is_dir() {
+        get_folder_content(path, &mut staging)
+    } else {
+        get_zip_content(path, &mut staging, progressbar)
+    }?;
+    content.staging = Some(staging);
+
+    Ok(content)
+}
+
+/// Reads an archive (zip, 7z, tar, tar.gz or tar.zst) and parses it into an unsorted list of ZipLiveryContent files
+/// The archive is streamed through once, livery files are extracted into the staging folder instead of memory
+/// Entries that would end up outside of Customs/Cars or Customs/Liveries are rejected, as are files not matching the manifest
+pub fn get_zip_content(zip_file: &PathBuf, staging: &mut StagingFolder, progressbar: &ProgressBar) -> Result<ArchiveContent> {
+    read_archive_content(zip_file, &archive::strip_extension(&get_filename(zip_file)), 0, staging, progressbar)
+}
+
+/// Reads all files within a folder (and it's subfolders), using the same layout detection as for archives
+/// Files are not loaded into memory, they are copied from their original location when written
+/// Archives within the folder are extracted into the staging folder
+pub fn get_folder_content(folder: &PathBuf, staging: &mut StagingFolder) -> Result<ArchiveContent> {
+    if !folder.is_dir() {
+        return Err(Error::NotFound(format!("folder {}", folder.display())));
+    }
+
+    let mut content = ArchiveContent::default();
+    let mut entries = Vec::<RawEntry>::new();
+    let mut verifier = Verifier::default();
+    for (entry, path) in walk_folder(folder) {
+        match normalize_entry_name(&entry) {
+            Ok(Some(components)) => {
+                match File::open(&path).and_then(|mut reader| verifier.check(&entry, &mut reader, &mut io::sink())) {
+                    Ok(hash) => content.hashes.insert(path.clone(), hash),
+                    Err(e) => {
+                        verifier.add_issue(&entry, format!("unreadable: {}", e));
+                        content.rejected.push(RejectedEntry { entry, reason: format!("unreadable: {}", e) });
+                        continue;
+                    }
+                };
+
+                let modified = super::get_modified(&path);
+                entries.push(RawEntry { entry, components, source: path, modified });
+            },
+            Ok(None) => (),
+            Err(reason) => content.rejected.push(RejectedEntry { entry, reason })
+        }
+    }
+
+    // Files at the root belong to a livery named after the folder, so `.` or `..` need the actual name
+    let folder_name = fs::canonicalize(folder).ok()
+        .and_then(|path| path.file_name().map(|name| name.to_string_lossy().to_string()))
+        .unwrap_or(DEFAULT_FOLDER_NAME.to_string());
+    content.verify = verifier.finish();
+    sort_entries(entries, &folder_name, 0, staging, &mut content);
+
+    Ok(content)
+}
+
+/// Lists all files within a folder and it's subfolders, sorted, with their path relative to the folder (using /)
+/// Symlinks are not followed, they could loop
+pub fn walk_folder(folder: &Path) -> Vec<(String, PathBuf)> {
+    fn walk(folder: &Path, prefix: &str, output: &mut Vec<(String, PathBuf)>) {
+        if let Ok(folder_content) = folder.read_dir() {
+            for item in folder_content.flatten() {
+                let entry = format!("{}{}", prefix, item.file_name().to_string_lossy());
+
+                match item.file_type() {
+                    Ok(file_type) if file_type.is_dir() => walk(&item.path(), format!("{}/", entry).as_str(), output),
+                    Ok(file_type) if file_type.is_file() => output.push((entry, item.path())),
+                    _ => ()
                 }
-                progressbar.inc(1);
             }
-            progressbar.finish();
-
-            return Some(content);
         }
     }
 
-    None
+    let mut files = Vec::<(String, PathBuf)>::new();
+    walk(folder, "", &mut files);
+    files.sort();
+    files
 }
 
-/// Parses the car.json and returns the folder in which the livery files are stored
-pub fn read_car_for_livery_folder(car_json: &ZipLiveryContent) -> Option<String> {
-    if let Ok(parsed_json) = super::read_json_from_bytes(car_json.file.clone()) {
-        // We get the livery folder from the car.json, if not found we just add the livery
-        if let Some(target_foldername) = parsed_json.get("customSkinName") {
-            if let Some(target_foldername) = target_foldername.as_str() {
-                if !target_foldername.is_empty() {
-                    return Some(target_foldername.to_string());
-                } else {
-                    return None;
+/// Streams an archive into the staging folder, archives found within are read recursively (up to MAX_ARCHIVE_DEPTH)
+/// The files are verified while they are extracted, an entry that can't be read (e.g. CRC mismatch) is rejected
+/// Only the entries of the outer archive advance the progressbar
+fn read_archive_content(path: &Path, default_folder: &str, depth: usize, staging: &mut StagingFolder, progressbar: &ProgressBar) -> Result<ArchiveContent> {
+    // Extracting, sorting out folders and entries that would escape
+    let mut content = ArchiveContent::default();
+    let mut entries = Vec::<RawEntry>::new();
+    let mut verifier = Verifier::default();
+    archive::for_each_entry(path, progressbar, |entry, reader, modified| {
+        match normalize_entry_name(entry) {
+            Ok(Some(components)) => {
+                let source = staging.next_file();
+                // Continuing, zip can still read the other entries, the other formats will fail on the next one anyway
+                match verifier.check(entry, reader, &mut fs::File::create(&source)?) {
+                    Ok(hash) => {
+                        content.hashes.insert(source.clone(), hash);
+                        entries.push(RawEntry { entry: entry.to_string(), components, source, modified });
+                    },
+                    Err(e) => {
+                        verifier.add_issue(entry, format!("corrupt: {}", e));
+                        content.rejected.push(RejectedEntry { entry: entry.to_string(), reason: format!("corrupt: {}", e) });
+                    }
                 }
+            },
+            Ok(None) => (), // Folder
+            Err(reason) => content.rejected.push(RejectedEntry { entry: entry.to_string(), reason })
+        }
+
+        Ok(())
+    })?;
+
+    content.verify = verifier.finish();
+    sort_entries(entries, default_folder, depth, staging, &mut content);
+
+    Ok(content)
+}
+
+/// Decides for every entry if it is a car.json or livery file and in which livery folder it belongs
+/// Archives within are opened, everything that would end up outside of Customs is rejected
+/// car.jsons are loaded into memory, livery files stay on disk
+/// A manifest at the root is not imported, the files are verified against it instead
+fn sort_entries(mut entries: Vec<RawEntry>, default_folder: &str, depth: usize, staging: &mut StagingFolder, content: &mut ArchiveContent) {
+    let mut components: Vec<Vec<String>> = entries.iter_mut().map(|raw| std::mem::take(&mut raw.components)).collect();
+    let default_folder = strip_wrapping_folders(&mut components).unwrap_or(default_folder.to_string());
+    let has_layout = components.iter().any(|components| get_entry_folder(components, &default_folder).1);
+    let mut own_manifest = None;
+    // Files of this level, nested archives are sorted on their own
+    let mut files = Vec::<ZipLiveryContent>::new();
+
+    for (raw, components) in entries.into_iter().zip(components) {
+        let name = components[components.len() - 1].clone();
+
+        if components.len() == 1 && name == manifest::MANIFEST_FILE {
+            match fs::read(&raw.source).ok().and_then(|data| PackManifest::from_bytes(data.as_slice())) {
+                Some(parsed) => own_manifest = Some(parsed),
+                None => content.rejected.push(RejectedEntry { entry: raw.entry, reason: "invalid manifest".to_string() })
             }
+            continue;
         }
+
+        // Archives within archives
+        if archive::detect_file(&raw.source).is_some() {
+            if depth + 1 >= MAX_ARCHIVE_DEPTH {
+                content.rejected.push(RejectedEntry { entry: raw.entry, reason: "archive nested too deep".to_string() });
+                continue;
+            }
+
+            match read_archive_content(&raw.source, &archive::strip_extension(&name), depth + 1, staging, &ProgressBar::hidden()) {
+                Ok(inner) => {
+                    content.files.extend(inner.files);
+                    if let Some(inner_manifest) = inner.manifest {
+                        match content.manifest.as_mut() {
+                            Some(existing) => existing.merge(inner_manifest),
+                            None => content.manifest = Some(inner_manifest)
+                        }
+                    }
+                    content.rejected.extend(inner.rejected.into_iter().map(|rejected| RejectedEntry { entry: format!("{}/{}", raw.entry, rejected.entry), reason: rejected.reason }));
+                    content.verify.checked += inner.verify.checked;
+                    content.verify.has_manifest |= inner.verify.has_manifest;
+                    content.verify.issues.extend(inner.verify.issues.into_iter().map(|issue| RejectedEntry { entry: format!("{}/{}", raw.entry, issue.entry), reason: issue.reason }));
+                    content.hashes.extend(inner.hashes);
+                },
+                Err(e) => content.rejected.push(RejectedEntry { entry: raw.entry, reason: format!("invalid nested archive: {}", e) })
+            }
+            continue;
+        }
+
+        let (mut upper, _) = get_entry_folder(&components, &default_folder);
+
+        // Without Cars folder we have to detect car.jsons by their content
+        let is_json = name.to_lowercase().ends_with(".json");
+        if !has_layout && is_json && fs::read(&raw.source).map(|data| CarJson::is_car_json(data.as_slice())).unwrap_or(false) {
+            upper = CustomFolder::Cars;
+        }
+
+        let file = if upper == CustomFolder::Cars {
+            match fs::read(&raw.source) {
+                Ok(data) => ZipLiveryContent { upper, name, file: data, modified: raw.modified, source: None },
+                Err(_) => continue
+            }
+        } else {
+            ZipLiveryContent { upper, name, file: Vec::<u8>::new(), modified: raw.modified, source: Some(raw.source) }
+        };
+
+        if let Err(reason) = file.check_safe() {
+            content.rejected.push(RejectedEntry { entry: raw.entry, reason });
+            continue;
+        }
+
+        files.push(file);
+    }
+
+    // A single car.json without layout decides where the loose livery files go, instead of the archive name
+    if !has_layout {
+        let mut cars = files.iter().filter(|file| file.upper == CustomFolder::Cars);
+        if let (Some(car), None) = (cars.next(), cars.next()) {
+            if let Some(folder) = read_car_for_livery_folder(car).filter(|folder| is_safe_name(folder)) {
+                for file in files.iter_mut().filter(|file| file.upper == CustomFolder::Liveries(default_folder.clone())) {
+                    file.upper = CustomFolder::Liveries(folder.clone());
+                }
+            }
+        }
+    }
+    content.files.extend(files);
+
+    if let Some(own_manifest) = own_manifest {
+        let (mismatching, rejected) = own_manifest.verify(&mut content.files, &content.hashes);
+        content.verify.has_manifest = true;
+        content.verify.issues.extend(mismatching.iter().cloned());
+        content.rejected.extend(mismatching);
+        content.rejected.extend(rejected);
+
+        match content.manifest.as_mut() {
+            Some(existing) => existing.merge(own_manifest),
+            None => content.manifest = Some(own_manifest)
+        }
+    }
+}
+
+/// Parses the car.json into the typed model
+pub fn read_car_json(car_json: &ZipLiveryContent) -> Option<CarJson> {
+    CarJson::from_bytes(car_json.file.as_slice())
+}
+
+/// Parses the car.json and returns the folder in which the livery files are stored
+pub fn read_car_for_livery_folder(car_json: &ZipLiveryContent) -> Option<String> {
+    // We get the livery folder from the car.json, if not found we just add the livery
+    read_car_json(car_json)?.livery_folder()
+}
+
+/// Groups the files of an archive, following the manifest if there is one
+pub fn group_content(files: Vec<ZipLiveryContent>, manifest: Option<&PackManifest>) -> Vec<Livery> {
+    match manifest {
+        Some(manifest) => manifest::group(files, manifest),
+        None => group_up(files)
     }
-    
-    None
 }
 
 /// Takes an unsorted list of Files and groups the car.jsons and livery files together
@@ -422,45 +872,89 @@ pub fn group_up(mut files: Vec<ZipLiveryContent>) -> Vec<Livery> {
 //     "bannerTemplateKey": 2
 // }
 
-pub fn write_livery_in_zip(livery: Livery) -> io::Result<String> {
-    let target_name = if let Some(liver) = livery.livery_folder.clone() {
-        liver
-    } else {
-        if let Some(car) = &livery.car_json {
-            car.name.clone()
-        } else {
-            String::new()
-        }
-    };
-    let target_name = format!("{}.zip", target_name);
+/// Writes the livery into an archive at the target, see export::ExportOptions for choosing the target
+pub fn write_livery_in_zip(livery: Livery, target: &Path, format: ArchiveFormat, compression: &CompressionOptions, progressbar: &ProgressBar) -> Result<()> {
+    write_liveries_in_zip(vec![livery], target, format, compression, progressbar)
+}
 
-    println!("Compressing Files...");
-    let progressbar = ProgressBar::new(livery.livery_files.len() as u64 + if livery.car_json.is_some() { 1 } else { 0 });
-    progressbar.set_style(ProgressStyle::with_template("[{elapsed_precise}] {bar:50.cyan/blue} {pos:>1}/{len:5}")
-                    .expect("Progress Style is valid (At least when it was typed, an update to indicatif might have broken it)")
-                    .progress_chars("##-"));
+/// Writes multiple liveries into one archive, with the same layout as a single one
+/// Files shared between liveries (e.g. two car.jsons pointing to the same folder) are only added once
+/// A manifest describing the liveries is added at the root, the progressbar is advanced per file
+pub fn write_liveries_in_zip(liveries: Vec<Livery>, target: &Path, format: ArchiveFormat, compression: &CompressionOptions, progressbar: &ProgressBar) -> Result<()> {
+    progressbar.inc_length(liveries.iter().map(|livery| livery.livery_files.len() as u64 + if livery.car_json.is_some() { 1 } else { 0 }).sum());
 
+    let manifest = PackManifest::new(&liveries)?;
+    let mut writer = ArchiveWriter::create(target, format, compression)?;
 
-    let buffer = File::create(&target_name)?;
-    let mut writer = zip::ZipWriter::new(buffer);
+    let res = match add_liveries(&mut writer, liveries, &manifest, progressbar) {
+        Ok(_) => writer.finish(),
+        Err(e) => {
+            drop(writer);
+            Err(e)
+        }
+    };
 
-    if let Some(car) = livery.car_json {
-        writer.start_file(car.get_interal_path(), zip::write::FileOptions::default())?;
-        writer.write_all(car.file.as_slice())?;
-        progressbar.inc(1);
+    // A partial archive would block the next export (when refusing to overwrite), so it is removed
+    if res.is_err() {
+        let _ = fs::remove_file(target);
     }
 
-    if let Some(_) = livery.livery_folder {
-        for item in livery.livery_files {
-            writer.start_file(item.get_interal_path(), zip::write::FileOptions::default())?;
-            writer.write_all(item.file.as_slice())?;
+    res
+}
+
+fn add_liveries(writer: &mut ArchiveWriter, liveries: Vec<Livery>, manifest: &PackManifest, progressbar: &ProgressBar) -> Result<()> {
+    let mut written = HashSet::<String>::new();
+
+    for livery in liveries {
+        let files = if livery.livery_folder.is_some() { livery.livery_files } else { Vec::new() };
+
+        for item in livery.car_json.into_iter().chain(files) {
+            let internal_path = item.get_interal_path();
+            if written.insert(internal_path.clone()) {
+                match &item.source {
+                    Some(source) => {
+                        let file = File::open(source)?;
+                        let size = file.metadata()?.len();
+                        writer.add_reader(&internal_path, BufReader::new(file), size, item.modified)?;
+                    },
+                    None => writer.add_file(&internal_path, item.file.as_slice(), item.modified)?
+                }
+            }
             progressbar.inc(1);
         }
     }
 
-    progressbar.finish();
+    writer.add_file(manifest::MANIFEST_FILE, manifest.to_bytes()?.as_slice(), Some(chrono::Local::now().naive_local()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_names() {
+        assert!(is_safe_name("#2_TeamIris_992"));
+        assert!(is_safe_name("decals.png"));
+
+        for name in ["", " ", ".", "..", "../Cars", "a/b", "a\\b", "C:", "C:evil", "/etc", "\\\\server", "nul\0"] {
+            assert!(!is_safe_name(name), "{:?} should be rejected", name);
+        }
+    }
+
+    #[test]
+    fn entry_names_are_split() {
+        assert_eq!(normalize_entry_name("Liveries/folder/decals.png"), Ok(Some(vec!["Liveries".to_string(), "folder".to_string(), "decals.png".to_string()])));
+        assert_eq!(normalize_entry_name("Cars\\./car.json"), Ok(Some(vec!["Cars".to_string(), "car.json".to_string()])));
+        assert_eq!(normalize_entry_name("Liveries/folder/"), Ok(None));
+        assert_eq!(normalize_entry_name("./"), Ok(None));
+    }
 
-    writer.finish()?;
-    
-    Ok(target_name)
+    #[test]
+    fn escaping_entry_names_are_rejected() {
+        for entry in ["../decals.png", "Liveries/../../decals.png", "..\\Cars\\car.json", "/etc/passwd", "\\Windows\\evil.dll", "C:/Windows/evil.dll", "C:evil.dll", "Liveries/D:\\evil.png", "Cars/a\0.json"] {
+            assert!(normalize_entry_name(entry).is_err(), "{:?} should be rejected", entry);
+        }
+    }
 }
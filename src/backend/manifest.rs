@@ -0,0 +1,183 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use chrono::Local;
+use serde::{Serialize, Deserialize};
+
+use crate::error::Result;
+
+use super::{livery_ops::{self, Livery, ZipLiveryContent, CustomFolder, RejectedEntry}, content_index, DATE_FORMAT_STR};
+
+/// Written at the root of every exported archive
+pub const MANIFEST_FILE: &str = "skinmanager_manifest.json";
+
+/// Describes the content of an exported archive, so importing doesn't have to guess which files belong together
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackManifest {
+    pub tool: String,
+    pub version: String,
+    pub exported: String,
+    pub liveries: Vec<ManifestLivery>
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ManifestLivery {
+    /// File name within Cars
+    pub car_json: Option<String>,
+    pub livery_folder: Option<String>,
+    pub car_model: Option<String>,
+    pub race_number: Option<i32>,
+    pub team: Option<String>,
+    pub files: Vec<ManifestFile>
+}
+
+/// A file of the archive, path as within Customs (e.g. Liveries/folder/decals.png)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub path: String,
+    pub size: u64,
+    pub hash: String
+}
+
+impl PackManifest {
+    /// Builds the manifest for the liveries, files shared between liveries are listed for each of them
+    pub fn new(liveries: &[Livery]) -> Result<PackManifest> {
+        let mut output = Vec::<ManifestLivery>::new();
+
+        for livery in liveries {
+            let car = livery.car_json.as_ref().and_then(livery_ops::read_car_json);
+            let files = if livery.livery_folder.is_some() { livery.livery_files.as_slice() } else { &[] };
+
+            let mut manifest_files = Vec::<ManifestFile>::new();
+            for item in livery.car_json.iter().chain(files.iter()) {
+                manifest_files.push(ManifestFile { path: item.get_interal_path(), size: item.get_size(), hash: get_hash(item, &HashMap::new())? });
+            }
+
+            output.push(ManifestLivery {
+                car_json: livery.car_json.as_ref().map(|car| car.name.clone()),
+                livery_folder: livery.livery_folder.clone(),
+                car_model: car.as_ref().map(|car| car.car_model_name()),
+                race_number: car.as_ref().and_then(|car| car.race_number),
+                team: car.as_ref().map(|car| car.team_name.clone().unwrap_or_default()),
+                files: manifest_files
+            });
+        }
+
+        Ok(PackManifest {
+            tool: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            exported: Local::now().format(&format!("{} %H:%M:%S", DATE_FORMAT_STR)).to_string(),
+            liveries: output
+        })
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Option<PackManifest> {
+        serde_json::from_slice(data).ok()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+
+    fn get_file(&self, internal_path: &str) -> Option<&ManifestFile> {
+        self.liveries.iter().flat_map(|livery| livery.files.iter()).find(|file| file.path == internal_path)
+    }
+
+    /// Checks the files against the hashes of the manifest
+    /// A livery with a file that doesn't match or is missing is rejected as a whole, its files are removed
+    /// Returns the files that don't match, and the other files rejected with their livery
+    /// Files not listed in the manifest are kept as they are
+    /// hashes holds the already known hashes by source file, the other files are hashed here
+    pub fn verify(&self, files: &mut Vec<ZipLiveryContent>, hashes: &HashMap<PathBuf, String>) -> (Vec<RejectedEntry>, Vec<RejectedEntry>) {
+        // Files that don't match, with the reason
+        let mut bad = Vec::<(String, String)>::new();
+
+        for item in files.iter() {
+            let internal_path = item.get_interal_path();
+            let expected = match self.get_file(&internal_path) {
+                Some(expected) => expected,
+                None => continue
+            };
+
+            match get_hash(item, hashes) {
+                Ok(hash) if hash == expected.hash && item.get_size() == expected.size => (),
+                _ => bad.push((internal_path, "content does not match the manifest".to_string()))
+            }
+        }
+
+        for file in self.liveries.iter().flat_map(|livery| livery.files.iter()) {
+            let found = files.iter().any(|item| item.get_interal_path() == file.path);
+            if !found && !bad.iter().any(|(path, _)| path == &file.path) {
+                bad.push((file.path.clone(), "listed in the manifest, but missing".to_string()));
+            }
+        }
+
+        // Every file of a livery listing a bad file, and the livery it is rejected with
+        let mut rejected_paths = Vec::<(String, String)>::new();
+        for livery in self.liveries.iter().filter(|livery| livery.files.iter().any(|file| bad.iter().any(|(path, _)| path == &file.path))) {
+            let name = livery.car_json.clone().or(livery.livery_folder.clone()).unwrap_or_default();
+            for file in livery.files.iter() {
+                if !rejected_paths.iter().any(|(path, _)| path == &file.path) {
+                    rejected_paths.push((file.path.clone(), name.clone()));
+                }
+            }
+        }
+
+        let mismatching = bad.iter().map(|(path, reason)| RejectedEntry { entry: path.clone(), reason: reason.clone() }).collect::<Vec<RejectedEntry>>();
+        let mut rejected = Vec::<RejectedEntry>::new();
+
+        files.retain(|item| {
+            let internal_path = item.get_interal_path();
+            match rejected_paths.iter().find(|(path, _)| path == &internal_path) {
+                Some((_, livery)) => {
+                    if !bad.iter().any(|(path, _)| path == &internal_path) {
+                        rejected.push(RejectedEntry { entry: internal_path, reason: format!("part of {}, which does not match the manifest", livery) });
+                    }
+                    false
+                },
+                None => true
+            }
+        });
+
+        (mismatching, rejected)
+    }
+
+    /// Adds the liveries of another manifest, e.g. from an archive within the archive
+    pub fn merge(&mut self, other: PackManifest) {
+        self.liveries.extend(other.liveries);
+    }
+}
+
+fn get_hash(item: &ZipLiveryContent, hashes: &HashMap<PathBuf, String>) -> Result<String> {
+    match &item.source {
+        Some(source) => match hashes.get(source) {
+            Some(hash) => Ok(hash.clone()),
+            None => content_index::hash_file(source)
+        },
+        None => Ok(content_index::hash_bytes(item.file.as_slice()))
+    }
+}
+
+/// Groups the files as listed in the manifest, files it doesn't list are grouped by livery_ops::group_up
+pub fn group(mut files: Vec<ZipLiveryContent>, manifest: &PackManifest) -> Vec<Livery> {
+    let mut liveries = Vec::<Livery>::new();
+
+    for entry in manifest.liveries.iter() {
+        let car_json = entry.car_json.as_ref().and_then(|name| {
+            let position = files.iter().position(|item| item.upper == CustomFolder::Cars && &item.name == name)?;
+            Some(files.remove(position))
+        });
+
+        let (livery_files, rest): (Vec<ZipLiveryContent>, Vec<ZipLiveryContent>) = files.into_iter()
+            .partition(|item| matches!((&item.upper, &entry.livery_folder), (CustomFolder::Liveries(folder), Some(listed)) if folder == listed));
+        files = rest;
+
+        if car_json.is_some() || !livery_files.is_empty() {
+            liveries.push(Livery { livery_folder: entry.livery_folder.clone(), car_json, livery_files });
+        }
+    }
+
+    liveries.extend(livery_ops::group_up(files));
+    liveries
+}
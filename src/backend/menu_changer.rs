@@ -3,7 +3,7 @@ use std::path::PathBuf;
 use json::JsonValue;
 use serde::{Serialize, Deserialize};
 
-use crate::State;
+use crate::{State, error::{Error, Result}};
 
 use super::SafeRead;
 
@@ -16,35 +16,35 @@ enum ConfigName {
 
 }
 
-fn get_config_file(filename: ConfigName, state: &State) -> Option<(PathBuf, JsonValue)> {
-    let name = match filename {
+fn get_config_name(filename: &ConfigName) -> &'static str {
+    match filename {
         ConfigName::MenuSettings => "menuSettings"
-    };
+    }
+}
 
-    return super::get_config_file(state, ACC_CONFIG_FOLDER_NAME, name);
+fn get_config_file(filename: ConfigName, state: &State) -> Result<(PathBuf, JsonValue)> {
+    super::get_config_file(state, ACC_CONFIG_FOLDER_NAME, get_config_name(&filename))
 }
 
-pub fn set_dds_generation(mode_state: bool, state: &State) -> Option<bool> {
-    if let Some((path, mut content)) = get_config_file(ConfigName::MenuSettings, state){
-        if let Some(old_state) = content.get("texDDS") {
-            if let Some(old_state) = old_state.as_i32() {
-                let state_i = match mode_state { true => 1, false => 0 };
-                
-                if old_state != state_i {
-                    //updating
-                    if content.set("texDDS", state_i.into()) {
-                        if super::write_json(path.as_path(), content).is_ok() {
-                            return Some(old_state ==  1);
-                        }
-                    }
-                } else {
-                    return Some(old_state ==  1);
-                }
-            }
+/// Error for a key (or a value of the wrong type) missing in the config file
+fn missing_key(filename: ConfigName, key: &str) -> Error {
+    Error::MissingConfigKey { file: format!("{}.{}", get_config_name(&filename), super::FILE_ENDING), key: key.to_string() }
+}
+
+pub fn set_dds_generation(mode_state: bool, state: &State) -> Result<bool> {
+    let (path, mut content) = get_config_file(ConfigName::MenuSettings, state)?;
+    let old_state = content.get("texDDS").and_then(|old_state| old_state.as_i32()).ok_or_else(|| missing_key(ConfigName::MenuSettings, "texDDS"))?;
+    let state_i = match mode_state { true => 1, false => 0 };
+
+    if old_state != state_i {
+        //updating
+        if !content.set("texDDS", state_i.into()) {
+            return Err(missing_key(ConfigName::MenuSettings, "texDDS"));
         }
+        super::write_json(path.as_path(), content)?;
     }
 
-    None
+    Ok(old_state == 1)
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -53,59 +53,35 @@ pub struct GraphicSettings {
     pub fullscreen: bool
 } 
 
-pub fn set_graphic_settings(settings: GraphicSettings, state: &State) -> Option<GraphicSettings> {
-    if let Some((path, mut content)) = get_config_file(ConfigName::MenuSettings, state){
-        if let Some(graphic) = content.get("graphicOptions") {
-            let mut graphic_new = graphic.clone();
-            
-            let full = if let Some(fullscreen) = graphic.get("useFullscreen") {
-                if let Some(fullscreen) = fullscreen.as_bool() {
-                    if settings.fullscreen != fullscreen {
-                        graphic_new.set("useFullscreen", settings.fullscreen.into());
-                    }
-
-                    fullscreen
-                } else {
-                    return None;
-                }
-            } else {
-                return None;
-            };
-
-            let res = if let Some(resolution) = graphic.get("resolution") {
-                if let (Some(old_x), Some(old_y))  = (resolution.get("x"), resolution.get("y")) {
-                    if let (Some(old_x), Some(old_y))  = (old_x.as_u32(), old_y.as_u32()) {
-                        if settings.resolution != (old_x, old_y) {
-                            let mut new_resultion = resolution.clone();
-                            new_resultion.set("x", settings.resolution.0.into());
-                            new_resultion.set("y", settings.resolution.1.into());
-
-                            graphic_new.set("resolution", new_resultion);
-                        }
-
-                        (old_x, old_y)
-                    } else {
-                        return None;
-                    }
-                } else {
-                    return None;
-                }
-            } else {
-                return None;
-            };
-
-            let old_settings = GraphicSettings { resolution: res, fullscreen: full };
-            if old_settings != settings {
-                content.set("graphicOptions", graphic_new);
-                if super::write_json(path.as_path(), content).is_ok() {
-                    return Some(old_settings);
-                }
-            } else {
-                return Some(old_settings);
-            }
-        }
+pub fn set_graphic_settings(settings: GraphicSettings, state: &State) -> Result<GraphicSettings> {
+    let (path, mut content) = get_config_file(ConfigName::MenuSettings, state)?;
+    let graphic = content.get("graphicOptions").ok_or_else(|| missing_key(ConfigName::MenuSettings, "graphicOptions"))?;
+    let mut graphic_new = graphic.clone();
+
+    let full = graphic.get("useFullscreen").and_then(|fullscreen| fullscreen.as_bool())
+        .ok_or_else(|| missing_key(ConfigName::MenuSettings, "graphicOptions.useFullscreen"))?;
+    if settings.fullscreen != full {
+        graphic_new.set("useFullscreen", settings.fullscreen.into());
+    }
+
+    let resolution = graphic.get("resolution").ok_or_else(|| missing_key(ConfigName::MenuSettings, "graphicOptions.resolution"))?;
+    let old_x = resolution.get("x").and_then(|old_x| old_x.as_u32()).ok_or_else(|| missing_key(ConfigName::MenuSettings, "graphicOptions.resolution.x"))?;
+    let old_y = resolution.get("y").and_then(|old_y| old_y.as_u32()).ok_or_else(|| missing_key(ConfigName::MenuSettings, "graphicOptions.resolution.y"))?;
+    if settings.resolution != (old_x, old_y) {
+        let mut new_resultion = resolution.clone();
+        new_resultion.set("x", settings.resolution.0.into());
+        new_resultion.set("y", settings.resolution.1.into());
+
+        graphic_new.set("resolution", new_resultion);
     }
-    None
+
+    let old_settings = GraphicSettings { resolution: (old_x, old_y), fullscreen: full };
+    if old_settings != settings {
+        content.set("graphicOptions", graphic_new);
+        super::write_json(path.as_path(), content)?;
+    }
+
+    Ok(old_settings)
 }
 
 
@@ -139,36 +115,28 @@ pub struct AudioSettings {
     pub music: f64
 } 
 
-pub fn set_audio_settings(settings: AudioSettings, state: &State) -> Option<AudioSettings> {
-    if let Some((path, mut content)) = get_config_file(ConfigName::MenuSettings, state){
-        if let Some(audio) = content.get("audio") {
-            let mut audio_new = audio.clone();
-
-            if let (Some(old_master), Some(old_music)) = (audio.get("main"),audio.get("music")) {
-                if let (Some(old_master), Some(old_music)) = (old_master.as_f64(), old_music.as_f64()) {
-                    if old_master != settings.master {
-                        audio_new.set("main", settings.master.into());
-                    }
-
-                    if old_music != settings.music {
-                        audio_new.set("music", settings.music.into());
-                    }
-
-                    let old_settings = AudioSettings {master: old_master, music: old_music};
-                    if old_settings != settings {
-                        content.set("audio", audio_new);
-                        if super::write_json(path.as_path(), content).is_ok() {
-                            return Some(old_settings);
-                        }
-                    } else {
-                        return Some(old_settings);
-                    }
-                }
-            }
-        }
+pub fn set_audio_settings(settings: AudioSettings, state: &State) -> Result<AudioSettings> {
+    let (path, mut content) = get_config_file(ConfigName::MenuSettings, state)?;
+    let audio = content.get("audio").ok_or_else(|| missing_key(ConfigName::MenuSettings, "audio"))?;
+    let mut audio_new = audio.clone();
+
+    let old_master = audio.get("main").and_then(|old_master| old_master.as_f64()).ok_or_else(|| missing_key(ConfigName::MenuSettings, "audio.main"))?;
+    let old_music = audio.get("music").and_then(|old_music| old_music.as_f64()).ok_or_else(|| missing_key(ConfigName::MenuSettings, "audio.music"))?;
+    if old_master != settings.master {
+        audio_new.set("main", settings.master.into());
+    }
+
+    if old_music != settings.music {
+        audio_new.set("music", settings.music.into());
+    }
+
+    let old_settings = AudioSettings {master: old_master, music: old_music};
+    if old_settings != settings {
+        content.set("audio", audio_new);
+        super::write_json(path.as_path(), content)?;
     }
 
-    None
+    Ok(old_settings)
 }
 
 // menuSettings.json:
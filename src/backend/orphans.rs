@@ -0,0 +1,99 @@
+use std::fmt::Display;
+
+use indicatif::ProgressBar;
+
+use crate::{State, error::{Error, Result}};
+
+use super::{library::{self, InstalledLivery}, livery_ops::{self, Livery, ZipLiveryContent}, archive::{ArchiveFormat, CompressionOptions}, export::{ExportOptions, IfExists}};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrphanKind {
+    /// car.json points to a livery folder that does not exist
+    MissingFolder,
+    /// car.json has an empty customSkinName
+    EmptySkinName,
+    /// Livery folder no car.json points to
+    UnusedFolder
+}
+
+#[derive(Debug, Clone)]
+pub struct Orphan {
+    pub kind: OrphanKind,
+    pub livery: InstalledLivery
+}
+
+impl Display for OrphanKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            OrphanKind::MissingFolder => "livery folder missing",
+            OrphanKind::EmptySkinName => "no customSkinName",
+            OrphanKind::UnusedFolder => "unused livery folder"
+        })
+    }
+}
+
+/// Finds all car.jsons and livery folders that don't have a counterpart
+pub fn find_orphans(state: &State) -> Vec<Orphan> {
+    let mut output = Vec::<Orphan>::new();
+
+    for liver in library::get_installed_liveries(state) {
+        let kind = if liver.car_file.is_none() {
+            OrphanKind::UnusedFolder
+        } else if liver.car.is_some() && liver.livery_folder.is_none() {
+            OrphanKind::EmptySkinName
+        } else if liver.livery_folder.is_some() && !liver.folder_exists {
+            OrphanKind::MissingFolder
+        } else {
+            continue;
+        };
+
+        output.push(Orphan { kind, livery: liver });
+    }
+
+    output
+}
+
+/// Deletes the files of this orphan
+pub fn delete(orphan: &Orphan, state: &State) -> Result<()> {
+    if let Some(car) = &orphan.livery.car_file {
+        livery_ops::delete_car_file(car, state)?;
+    }
+
+    if let (OrphanKind::UnusedFolder, Some(folder)) = (&orphan.kind, &orphan.livery.livery_folder) {
+        livery_ops::delete_livery_folder(folder, state)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the orphan into a zip (in the current folder, versioned if the name is taken), it is not deleted
+pub fn archive(orphan: &Orphan, state: &State) -> Result<String> {
+    let car_json = match &orphan.livery.car_file {
+        Some(car) => Some(livery_ops::get_car_file(car, state)?),
+        None => None
+    };
+
+    let (livery_folder, livery_files) = match (&orphan.kind, &orphan.livery.livery_folder) {
+        (OrphanKind::UnusedFolder, Some(folder)) => (Some(folder.clone()), livery_ops::get_livery_files(folder, state).unwrap_or_default()),
+        _ => (None, Vec::<ZipLiveryContent>::new())
+    };
+
+    let livery = Livery { livery_folder, car_json, livery_files };
+    let target = ExportOptions { if_exists: IfExists::Version, ..ExportOptions::default() }.get_target(&livery.get_name(), ArchiveFormat::Zip)?;
+    livery_ops::write_livery_in_zip(livery, &target, ArchiveFormat::Zip, &CompressionOptions::default(), &ProgressBar::hidden())?;
+
+    Ok(target.display().to_string())
+}
+
+/// Links the orphan with a counterpart
+/// For a car.json `target` is the livery folder it should point to, for an unused folder it is the car.json that should point to it
+pub fn relink(orphan: &Orphan, target: &String, state: &State) -> Result<()> {
+    let (car_file, folder) = match (&orphan.kind, &orphan.livery.livery_folder, &orphan.livery.car_file) {
+        (OrphanKind::UnusedFolder, Some(folder), _) => (target, folder),
+        (_, _, Some(car)) => (car, target),
+        _ => return Err(Error::NotFound("car.json or livery folder to link".to_string()))
+    };
+
+    let car_json = livery_ops::get_car_file(car_file, state)?;
+    livery_ops::set_car_livery_folder(&car_json, folder, state)
+}
@@ -0,0 +1,45 @@
+use std::{path::PathBuf, fs};
+
+use serde::{Serialize, Deserialize};
+
+use crate::error::{Error, Result};
+
+/// Folder within the config folder of the user (~/.config, AppData/Roaming...)
+pub const CONFIG_FOLDER_NAME: &str = "acc_skinmanager";
+pub const CONFIG_FILE: &str = "config.json";
+
+/// Settings needed before the ACC folder is known, so they can't go into Apps/Skinmanager
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ToolConfig {
+    /// ACC documents folder used instead of detecting it
+    pub acc_dir: Option<PathBuf>
+}
+
+pub fn get_config_path() -> Option<PathBuf> {
+    let mut path = dirs::config_dir()?;
+    path.push(CONFIG_FOLDER_NAME);
+    path.push(CONFIG_FILE);
+
+    Some(path)
+}
+
+impl ToolConfig {
+    /// Reads the config, defaults if there is none (or it can't be read)
+    pub fn load() -> ToolConfig {
+        get_config_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|data| serde_json::from_str(data.as_str()).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = get_config_path().ok_or_else(|| Error::NotFound("config folder of the user".to_string()))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let data = serde_json::to_string_pretty(self)?;
+        Ok(fs::write(path, data)?)
+    }
+}
@@ -0,0 +1,311 @@
+use std::{path::{Path, PathBuf}, fs, collections::HashMap};
+
+use crate::{State, error::{Error, Result}};
+
+use super::{livery_ops::{self, ZipLiveryContent}, backup::{self, JournalEntry}};
+
+/// A file that was moved into place, with where the file it replaced was moved to
+#[derive(Debug)]
+struct AppliedFile {
+    target: PathBuf,
+    previous: Option<PathBuf>
+}
+
+/// Groups all writes of an install, so they can be undone as a whole
+/// Files are first written into a folder within the temp folder and only moved into place on commit, once every livery is staged
+/// Replaced files are kept there until the commit backed them up, and everything is rolled back if the transaction is dropped without commit
+#[derive(Debug)]
+pub struct Transaction {
+    folder: PathBuf,
+    counter: usize,
+    pending: Vec<(PathBuf, PathBuf)>,
+    applied: Vec<AppliedFile>,
+    created_folders: Vec<PathBuf>,
+    /// Files of the import staging folder that were moved in, with where they are now, in case one is staged twice
+    moved: HashMap<PathBuf, PathBuf>,
+    done: bool
+}
+
+impl Transaction {
+    pub fn begin(state: &State) -> Result<Transaction> {
+        let mut folder = livery_ops::get_temp_folder(state)?;
+        folder.push(format!("install-{}-{}", std::process::id(), chrono::Local::now().format("%Y%m%d%H%M%S%f")));
+
+        fs::create_dir_all(&folder)?;
+
+        Ok(Transaction { folder, counter: 0, pending: Vec::new(), applied: Vec::new(), created_folders: Vec::new(), moved: HashMap::new(), done: false })
+    }
+
+    fn next_file(&mut self) -> PathBuf {
+        self.counter += 1;
+
+        let mut file = self.folder.clone();
+        file.push(self.counter.to_string());
+        file
+    }
+
+    /// Writes the content into the temp folder, it is moved to the target on apply
+    /// Files extracted into the import staging folder are on the same drive, so they are moved instead of copied
+    pub fn stage(&mut self, item: &ZipLiveryContent, target: PathBuf) -> Result<()> {
+        let staged = self.next_file();
+        let temp_folder = self.folder.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        match &item.source {
+            Some(source) if source.starts_with(&temp_folder) => match self.moved.get(source) {
+                Some(moved) => fs::copy(moved, &staged).map(|_| ())?,
+                None => {
+                    fs::rename(source, &staged)?;
+                    self.moved.insert(source.clone(), staged.clone());
+                }
+            },
+            _ => item.write_to(&staged)?
+        }
+
+        self.pending.push((staged, target));
+        Ok(())
+    }
+
+    /// Moves all staged files into place
+    /// On error the files moved so far stay in place, they are undone together with the rest on rollback
+    fn apply(&mut self) -> Result<()> {
+        for (staged, target) in std::mem::take(&mut self.pending) {
+            if let Some(parent) = target.parent() {
+                self.create_folder(parent)?;
+            }
+
+            let previous = if target.exists() {
+                let backup = self.next_file();
+                fs::rename(&target, &backup)?;
+                Some(backup)
+            } else {
+                None
+            };
+
+            if let Err(e) = fs::rename(&staged, &target) {
+                if let Some(backup) = previous {
+                    fs::rename(backup, &target)?;
+                }
+                return Err(e.into());
+            }
+
+            self.applied.push(AppliedFile { target, previous });
+        }
+
+        Ok(())
+    }
+
+    /// Creates the folder (and missing parents), remembering which ones have to be removed on rollback
+    fn create_folder(&mut self, folder: &Path) -> Result<()> {
+        if folder.exists() {
+            return Ok(());
+        }
+
+        if let Some(parent) = folder.parent() {
+            self.create_folder(parent)?;
+        }
+
+        fs::create_dir(folder)?;
+        self.created_folders.push(folder.to_path_buf());
+        Ok(())
+    }
+
+    /// Number of files staged so far
+    pub fn get_staged_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Moves all staged files into place, the install is added to the journal
+    /// The files that get replaced are backed up before anything is moved, so even an interrupted install can be undone
+    /// `source` is the archive or folder that was installed, nothing is recorded if no file was staged
+    /// If the backup fails nothing is moved, if a file can't be moved into place everything is rolled back
+    pub fn commit(mut self, source: &str, state: &State) -> Result<Option<JournalEntry>> {
+        self.done = true;
+        if self.pending.is_empty() {
+            let _ = fs::remove_dir_all(&self.folder);
+            return Ok(None);
+        }
+
+        // Only the first write of a file replaces what was there before the install
+        let mut created = Vec::<String>::new();
+        let mut replaced = Vec::<(String, PathBuf)>::new();
+        for (_, target) in self.pending.iter() {
+            let internal_path = match backup::get_internal_path(target, state) {
+                Some(internal_path) => internal_path,
+                None => continue
+            };
+            if created.contains(&internal_path) || replaced.iter().any(|(path, _)| path == &internal_path) {
+                continue;
+            }
+
+            if target.exists() {
+                replaced.push((internal_path, target.clone()));
+            } else {
+                created.push(internal_path);
+            }
+        }
+
+        let entry = match backup::record(source, created, replaced, state) {
+            Ok(entry) => entry,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&self.folder);
+                return Err(e);
+            }
+        };
+
+        if let Err(e) = self.apply() {
+            if let Err(undo_error) = self.undo() {
+                // The replaced files are still in the backup (and the temp folder), undoing the journal entry restores them
+                return Err(Error::Io(std::io::Error::other(format!("{}, rolling back failed as well: {}, install #{} can still be undone", e, undo_error, entry.id))));
+            }
+
+            let _ = backup::discard(&entry, state);
+            let _ = fs::remove_dir_all(&self.folder);
+            return Err(e);
+        }
+
+        let _ = fs::remove_dir_all(&self.folder);
+        Ok(Some(entry))
+    }
+
+    /// Undoes all changes, restoring the replaced files
+    pub fn rollback(mut self) -> Result<()> {
+        self.done = true;
+        let res = self.undo();
+        let _ = fs::remove_dir_all(&self.folder);
+
+        res
+    }
+
+    /// Restores as much as possible, a file that can't be restored doesn't stop the others
+    fn undo(&mut self) -> Result<()> {
+        let mut failed = Vec::<String>::new();
+
+        // Going backwards, in case a file was written multiple times
+        while let Some(file) = self.applied.pop() {
+            let res = match file.previous {
+                Some(previous) => fs::rename(previous, &file.target),
+                None => fs::remove_file(&file.target)
+            };
+            if let Err(e) = res {
+                failed.push(format!("{} ({})", file.target.display(), e));
+            }
+        }
+
+        while let Some(folder) = self.created_folders.pop() {
+            if let Err(e) = fs::remove_dir(&folder) {
+                failed.push(format!("{} ({})", folder.display(), e));
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Io(std::io::Error::other(format!("{} files or folders could not be restored: {}", failed.len(), failed.join(", ")))))
+        }
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.undo();
+            let _ = fs::remove_dir_all(&self.folder);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::livery_ops::{CustomFolder, ACC_CUSTOMS_FOLDER_NAME};
+
+    fn get_test_state(name: &str) -> State {
+        let root = std::env::temp_dir().join(format!("acc_skinmanager-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(ACC_CUSTOMS_FOLDER_NAME).join("Cars")).expect("temp folder is writable");
+
+        State::new(root)
+    }
+
+    fn get_content(upper: CustomFolder, name: &str, data: &str) -> ZipLiveryContent {
+        ZipLiveryContent { upper, name: name.to_string(), file: data.as_bytes().to_vec(), modified: None, source: None }
+    }
+
+    #[test]
+    fn rollback_restores_replaced_and_removes_created() {
+        let state = get_test_state("rollback");
+        let car = get_content(CustomFolder::Cars, "car.json", "new");
+        let decals = get_content(CustomFolder::Liveries("folder".to_string()), "decals.png", "new");
+        fs::write(car.get_target(&state), "old").expect("writable");
+
+        let mut transaction = Transaction::begin(&state).expect("temp folder");
+        transaction.stage(&car, car.get_target(&state)).expect("staged");
+        transaction.stage(&decals, decals.get_target(&state)).expect("staged");
+        transaction.apply().expect("applied");
+        assert_eq!(fs::read_to_string(car.get_target(&state)).expect("written"), "new");
+
+        transaction.rollback().expect("rolled back");
+        assert_eq!(fs::read_to_string(car.get_target(&state)).expect("restored"), "old");
+        assert!(!decals.get_target(&state).exists());
+        assert!(!state.root_folder.join(ACC_CUSTOMS_FOLDER_NAME).join("Liveries").exists());
+
+        let _ = fs::remove_dir_all(&state.root_folder);
+    }
+
+    #[test]
+    fn rollback_continues_after_a_failure() {
+        let state = get_test_state("rollback-failure");
+        let car = get_content(CustomFolder::Cars, "car.json", "new");
+        let decals = get_content(CustomFolder::Liveries("folder".to_string()), "decals.png", "new");
+        fs::write(car.get_target(&state), "old").expect("writable");
+
+        let mut transaction = Transaction::begin(&state).expect("temp folder");
+        transaction.stage(&decals, decals.get_target(&state)).expect("staged");
+        transaction.stage(&car, car.get_target(&state)).expect("staged");
+        transaction.apply().expect("applied");
+
+        // The replaced car.json is gone, so it can't be restored
+        let previous = transaction.applied.iter().find_map(|file| file.previous.clone()).expect("car.json was replaced");
+        fs::remove_file(previous).expect("removable");
+
+        assert!(transaction.rollback().is_err());
+        assert!(!decals.get_target(&state).exists());
+
+        let _ = fs::remove_dir_all(&state.root_folder);
+    }
+
+    #[test]
+    fn drop_without_commit_rolls_back() {
+        let state = get_test_state("drop");
+        let car = get_content(CustomFolder::Cars, "car.json", "new");
+
+        let mut transaction = Transaction::begin(&state).expect("temp folder");
+        transaction.stage(&car, car.get_target(&state)).expect("staged");
+        transaction.apply().expect("applied");
+        drop(transaction);
+
+        assert!(!car.get_target(&state).exists());
+
+        let _ = fs::remove_dir_all(&state.root_folder);
+    }
+
+    #[test]
+    fn damaged_journal_is_not_overwritten() {
+        let state = get_test_state("journal");
+        let car = get_content(CustomFolder::Cars, "car.json", "new");
+        fs::write(car.get_target(&state), "old").expect("writable");
+
+        let journal = state.root_folder.join(crate::backend::app_data::ACC_APP_FOLDER_NAME).join(backup::JOURNAL_FILE);
+        fs::create_dir_all(journal.parent().expect("has a parent")).expect("writable");
+        fs::write(&journal, "[{\"id\": 1,").expect("writable");
+
+        let mut transaction = Transaction::begin(&state).expect("temp folder");
+        transaction.stage(&car, car.get_target(&state)).expect("staged");
+        assert!(transaction.commit("pack.zip", &state).is_err());
+
+        assert_eq!(fs::read_to_string(&journal).expect("kept"), "[{\"id\": 1,");
+        assert_eq!(fs::read_to_string(car.get_target(&state)).expect("kept"), "old");
+
+        let _ = fs::remove_dir_all(&state.root_folder);
+    }
+}
@@ -0,0 +1,324 @@
+use std::{path::Path, fs::File, io::{self, Read, Write}, collections::HashSet};
+
+use indicatif::ProgressBar;
+use sha2::{Digest, Sha256};
+
+use crate::model::CarJson;
+
+use super::{archive, livery_ops::{self, RejectedEntry}, manifest::{self, PackManifest}};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const PNG_END: &[u8] = b"IEND\xAE\x42\x60\x82";
+const DDS_MAGIC: &[u8] = b"DDS ";
+/// Size of the DDS header after the magic, as stored in the header itself
+const DDS_HEADER_SIZE: u32 = 124;
+/// Bytes kept from the start of each file, enough for the png and dds checks
+const HEAD_SIZE: usize = 16;
+/// Json files of a livery folder, they sit beside the livery files but are not car.jsons
+const LIVERY_JSONS: [&str; 2] = ["decals.json", "sponsors.json"];
+
+/// Result of checking an archive or folder
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Number of files that were checked
+    pub checked: usize,
+    /// Bad entries, entry is empty if the archive itself is damaged
+    pub issues: Vec<RejectedEntry>,
+    pub has_manifest: bool
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// A checked file, kept for comparing against the manifest
+#[derive(Debug)]
+struct CheckedFile {
+    entry: String,
+    size: u64,
+    hash: String
+}
+
+/// What is kept of a file while streaming it, only json files are held completely
+struct ReadFile {
+    size: u64,
+    hash: String,
+    head: Vec<u8>,
+    has_png_end: bool,
+    data: Option<Vec<u8>>
+}
+
+/// Reads the file in chunks, hashing it and looking for the end of a png on the way
+/// Every chunk is also written to output, use io::sink() to only check the file
+fn read_file(entry: &str, reader: &mut dyn Read, output: &mut dyn Write) -> io::Result<ReadFile> {
+    let keep_data = entry.to_lowercase().ends_with(".json");
+    let mut file = ReadFile { size: 0, hash: String::new(), head: Vec::new(), has_png_end: false, data: keep_data.then(Vec::new) };
+    let mut hasher = Sha256::new();
+    let mut buffer = [0_u8; 64 * 1024];
+    // End of the previous chunk, so an IEND split between two chunks is found
+    let mut window = Vec::<u8>::new();
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        let chunk = &buffer[..read];
+
+        output.write_all(chunk)?;
+        hasher.update(chunk);
+        file.size += read as u64;
+        if file.head.len() < HEAD_SIZE {
+            file.head.extend(chunk.iter().take(HEAD_SIZE - file.head.len()));
+        }
+        if let Some(data) = file.data.as_mut() {
+            data.extend_from_slice(chunk);
+        }
+
+        if !file.has_png_end {
+            window.extend_from_slice(chunk);
+            file.has_png_end = window.windows(PNG_END.len()).any(|part| part == PNG_END);
+            window.drain(..window.len().saturating_sub(PNG_END.len() - 1));
+        }
+    }
+
+    file.hash = format!("{:x}", hasher.finalize());
+    Ok(file)
+}
+
+/// Checks the files of an archive or folder while they are streamed, so the import can verify in the same pass it extracts
+#[derive(Debug, Default)]
+pub struct Verifier {
+    report: VerifyReport,
+    files: Vec<CheckedFile>,
+    /// Json files, checked at the end once it is known which folders hold livery files
+    jsons: Vec<(String, Vec<u8>)>,
+    livery_file_folders: HashSet<String>,
+    /// The manifest closest to the root, with its content
+    manifest: Option<(String, Vec<u8>)>
+}
+
+impl Verifier {
+    /// Streams the entry into output while checking it, returns the SHA-256 of the content
+    /// Bad content is reported, but only a failing read (e.g. a CRC mismatch) is an Err, add it with add_issue
+    pub fn check(&mut self, entry: &str, reader: &mut dyn Read, output: &mut dyn Write) -> io::Result<String> {
+        let entry = entry.replace('\\', "/");
+        self.report.checked += 1;
+        let file = read_file(&entry, reader, output)?;
+
+        if let Err(reason) = check_content(&entry, &file) {
+            self.add_issue(&entry, reason);
+        }
+
+        let lower = entry.to_lowercase();
+        if lower.ends_with(".png") || lower.ends_with(".dds") {
+            self.livery_file_folders.insert(get_parent(&lower).to_string());
+        }
+
+        if let Some(data) = file.data {
+            if get_file_name(&entry) != manifest::MANIFEST_FILE {
+                self.jsons.push((entry.clone(), data));
+            } else if self.manifest.as_ref().map(|(name, _)| name.len() > entry.len()).unwrap_or(true) {
+                self.manifest = Some((entry.clone(), data));
+            }
+        }
+
+        self.files.push(CheckedFile { entry, size: file.size, hash: file.hash.clone() });
+        Ok(file.hash)
+    }
+
+    pub fn add_issue(&mut self, entry: &str, reason: String) {
+        self.report.issues.push(RejectedEntry { entry: entry.to_string(), reason });
+    }
+
+    /// Checks the car.jsons, the manifest is left to the caller
+    pub fn finish(mut self) -> VerifyReport {
+        for (entry, data) in self.jsons.iter() {
+            if is_car_json_entry(entry, data, &self.livery_file_folders) && CarJson::from_bytes(data).is_none() {
+                self.report.issues.push(RejectedEntry { entry: entry.clone(), reason: "car.json can't be parsed".to_string() });
+            }
+        }
+
+        self.report
+    }
+}
+
+/// Checks every file of an archive (or folder) without installing anything
+/// Archives are read completely, so zip and 7z verify the CRC of each entry, and truncated archives are reported
+/// car.jsons have to parse, png and dds files need a valid header,
+/// and if the archive has a manifest, the files have to match its hashes
+/// Archives within the archive are only checked as a whole (CRC), not their content
+pub fn verify(path: &Path) -> VerifyReport {
+    let mut verifier = Verifier::default();
+
+    if path.is_dir() {
+        for (entry, file) in livery_ops::walk_folder(path) {
+            if let Err(e) = File::open(&file).and_then(|mut reader| verifier.check(&entry, &mut reader, &mut io::sink())) {
+                verifier.add_issue(&entry, format!("unreadable: {}", e));
+            }
+        }
+    } else {
+        let res = archive::for_each_entry(path, &ProgressBar::hidden(), |entry, reader, _| {
+            // Continuing, zip can still read the other entries, the other formats will fail on the next one anyway
+            if let Err(e) = verifier.check(entry, reader, &mut io::sink()) {
+                verifier.add_issue(entry, format!("corrupt: {}", e));
+            }
+
+            Ok(())
+        });
+
+        if let Err(e) = res {
+            verifier.add_issue("", format!("archive can't be read completely (truncated or damaged): {}", e));
+        }
+    }
+
+    let manifest = verifier.manifest.take();
+    let files = std::mem::take(&mut verifier.files);
+    let mut report = verifier.finish();
+
+    if let Some((entry, data)) = manifest {
+        report.has_manifest = true;
+        match PackManifest::from_bytes(data.as_slice()) {
+            Some(manifest) => check_manifest(&manifest, &files, &mut report),
+            None => report.issues.push(RejectedEntry { entry, reason: "invalid manifest".to_string() })
+        }
+    }
+
+    report
+}
+
+/// A json is a car.json within a Cars folder, with a carModelType key, or beside livery files (outside of Liveries, like a flat pack)
+fn is_car_json_entry(entry: &str, data: &[u8], livery_file_folders: &HashSet<String>) -> bool {
+    let lower = entry.to_lowercase();
+    let in_folder = |folder: &str| lower.starts_with(&format!("{}/", folder)) || lower.contains(&format!("/{}/", folder));
+
+    in_folder("cars")
+        || CarJson::is_car_json(data)
+        // A damaged car.json doesn't parse, but still shows the key
+        || String::from_utf8_lossy(data).contains("\"carModelType\"")
+        || (livery_file_folders.contains(get_parent(&lower)) && !in_folder("liveries") && !LIVERY_JSONS.contains(&get_file_name(&lower)))
+}
+
+fn get_parent(entry: &str) -> &str {
+    entry.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("")
+}
+
+fn get_file_name(entry: &str) -> &str {
+    entry.rsplit(['/', '\\']).next().unwrap_or(entry)
+}
+
+/// Checks a single file by its extension
+/// A png only needs an IEND chunk somewhere, data after it is ignored (like image viewers and ACC do)
+fn check_content(entry: &str, file: &ReadFile) -> Result<(), String> {
+    let lower = entry.to_lowercase().replace('\\', "/");
+    let head = file.head.as_slice();
+
+    if lower.ends_with(".png") {
+        if !head.starts_with(PNG_SIGNATURE) || head.get(12..16) != Some(b"IHDR".as_slice()) {
+            return Err("invalid png header".to_string());
+        }
+        if !file.has_png_end {
+            return Err("png is truncated".to_string());
+        }
+    } else if lower.ends_with(".dds") {
+        let header_size = head.get(4..8).map(|size| u32::from_le_bytes([size[0], size[1], size[2], size[3]]));
+        if !head.starts_with(DDS_MAGIC) || header_size != Some(DDS_HEADER_SIZE) || file.size < (DDS_MAGIC.len() as u32 + DDS_HEADER_SIZE) as u64 {
+            return Err("invalid dds header".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+/// Compares the files with the manifest, the entries may be within a wrapping folder
+/// Entries that could not be read are already reported, so they are not reported as missing again
+fn check_manifest(manifest: &PackManifest, files: &[CheckedFile], report: &mut VerifyReport) {
+    for listed in manifest.liveries.iter().flat_map(|livery| livery.files.iter()) {
+        let suffix = format!("/{}", listed.path);
+        if report.issues.iter().any(|issue| issue.entry == listed.path || issue.entry.ends_with(suffix.as_str())) {
+            continue;
+        }
+
+        match files.iter().find(|file| file.entry == listed.path || file.entry.ends_with(suffix.as_str())) {
+            Some(file) if file.size != listed.size || file.hash != listed.hash => {
+                report.issues.push(RejectedEntry { entry: file.entry.clone(), reason: "content does not match the manifest".to_string() });
+            },
+            Some(_) => (),
+            None => report.issues.push(RejectedEntry { entry: listed.path.clone(), reason: "listed in the manifest, but missing".to_string() })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(trailing: &[u8]) -> Vec<u8> {
+        let mut data = PNG_SIGNATURE.to_vec();
+        data.extend_from_slice(b"\0\0\0\x0dIHDR");
+        data.extend_from_slice(&[0_u8; 17]);
+        data.extend_from_slice(b"\0\0\0\0");
+        data.extend_from_slice(PNG_END);
+        data.extend_from_slice(trailing);
+        data
+    }
+
+    fn check(entry: &str, data: &[u8]) -> Result<(), String> {
+        let file = read_file(entry, &mut &data[..], &mut io::sink()).expect("reading from memory can't fail");
+        check_content(entry, &file)
+    }
+
+    #[test]
+    fn png_end() {
+        assert!(check("decals.png", &png(b"")).is_ok());
+        assert!(check("decals.png", &png(b"trailing data")).is_ok());
+
+        let data = png(b"");
+        assert_eq!(check("decals.png", &data[..data.len() - 1]), Err("png is truncated".to_string()));
+        assert_eq!(check("decals.png", b"not a png at all"), Err("invalid png header".to_string()));
+    }
+
+    #[test]
+    fn png_end_across_chunks() {
+        // The IEND chunk starts right before the end of the first 64 KiB read
+        let mut data = png(b"");
+        data.truncate(data.len() - PNG_END.len());
+        data.resize(64 * 1024 - 3, 0);
+        data.extend_from_slice(PNG_END);
+
+        assert!(check("decals.png", &data).is_ok());
+    }
+
+    #[test]
+    fn car_jsons_are_found() {
+        let mut verifier = Verifier::default();
+        let files: [(&str, &[u8]); 6] = [
+            ("Team_X/decals.png", &png(b"")),
+            ("Team_X/decals.json", b"not json"),
+            ("Team_X/car.json", b"{ \"carModelType\": "),
+            ("Extra/renamed.json", b"{ \"carModelType\": 34 } }"),
+            ("Extra/notes.json", b"not json"),
+            ("Team_X/Liveries/Team_X/sponsors.json", b"not json")
+        ];
+        for (entry, data) in files {
+            verifier.check(entry, &mut &data[..], &mut io::sink()).expect("reading from memory can't fail");
+        }
+
+        let report = verifier.finish();
+        let bad: Vec<&str> = report.issues.iter().map(|issue| issue.entry.as_str()).collect();
+        assert_eq!(bad, vec!["Team_X/car.json", "Extra/renamed.json"]);
+        assert_eq!(report.checked, 6);
+    }
+
+    #[test]
+    fn streamed_hash() {
+        let data = png(b"trailing data");
+        let file = read_file("decals.png", &mut &data[..], &mut io::sink()).expect("reading from memory can't fail");
+
+        assert_eq!(file.hash, crate::backend::content_index::hash_bytes(&data));
+        assert_eq!(file.size, data.len() as u64);
+        assert!(file.data.is_none());
+    }
+}
@@ -0,0 +1,152 @@
+use clap::ValueEnum;
+
+use acc_skinmanager::backend::{install, archive, export, history};
+
+// Command line versions of the backend enums, so the library doesn't depend on clap
+
+/// How to resolve a conflict without asking the user
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ConflictPolicy {
+    /// Replace the installed files
+    Overwrite,
+    /// Keep the installed files and don't install this livery
+    Skip,
+    /// Install under a new, free name
+    Rename,
+    /// Overwrite if the files in the archive are newer than the installed ones, else skip
+    NewerWins,
+    /// Abort the install
+    Fail
+}
+
+impl From<ConflictPolicy> for install::ConflictPolicy {
+    fn from(policy: ConflictPolicy) -> Self {
+        match policy {
+            ConflictPolicy::Overwrite => install::ConflictPolicy::Overwrite,
+            ConflictPolicy::Skip => install::ConflictPolicy::Skip,
+            ConflictPolicy::Rename => install::ConflictPolicy::Rename,
+            ConflictPolicy::NewerWins => install::ConflictPolicy::NewerWins,
+            ConflictPolicy::Fail => install::ConflictPolicy::Fail
+        }
+    }
+}
+
+/// Archive formats livery packs can be imported from and exported to
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ArchiveFormat {
+    Zip,
+    #[value(name = "7z")]
+    SevenZ,
+    Tar,
+    TarGz,
+    TarZst
+}
+
+impl From<ArchiveFormat> for archive::ArchiveFormat {
+    fn from(format: ArchiveFormat) -> Self {
+        match format {
+            ArchiveFormat::Zip => archive::ArchiveFormat::Zip,
+            ArchiveFormat::SevenZ => archive::ArchiveFormat::SevenZ,
+            ArchiveFormat::Tar => archive::ArchiveFormat::Tar,
+            ArchiveFormat::TarGz => archive::ArchiveFormat::TarGz,
+            ArchiveFormat::TarZst => archive::ArchiveFormat::TarZst
+        }
+    }
+}
+
+/// How files are compressed within zip archives
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CompressionMethod {
+    Store,
+    Deflate,
+    Bzip2,
+    Zstd
+}
+
+impl From<CompressionMethod> for archive::CompressionMethod {
+    fn from(method: CompressionMethod) -> Self {
+        match method {
+            CompressionMethod::Store => archive::CompressionMethod::Store,
+            CompressionMethod::Deflate => archive::CompressionMethod::Deflate,
+            CompressionMethod::Bzip2 => archive::CompressionMethod::Bzip2,
+            CompressionMethod::Zstd => archive::CompressionMethod::Zstd
+        }
+    }
+}
+
+/// Parses EXT=METHOD (e.g. dds=zstd)
+pub fn parse_extension_method(text: &str) -> Result<(String, archive::CompressionMethod), String> {
+    let (extension, method) = text.split_once('=').ok_or(format!("expected EXT=METHOD, got {}", text))?;
+    let method = CompressionMethod::from_str(method.trim(), true)?;
+
+    Ok((extension.trim().trim_start_matches('.').to_string(), method.into()))
+}
+
+/// What to do if the archive to export into already exists
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum IfExists {
+    /// Abort the export of this archive
+    Refuse,
+    /// Append -2, -3... to the name
+    Version,
+    /// Replace the existing archive
+    Overwrite
+}
+
+impl From<IfExists> for export::IfExists {
+    fn from(if_exists: IfExists) -> Self {
+        match if_exists {
+            IfExists::Refuse => export::IfExists::Refuse,
+            IfExists::Version => export::IfExists::Version,
+            IfExists::Overwrite => export::IfExists::Overwrite
+        }
+    }
+}
+
+/// Which livery files are exported, the car.json is always included
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ExportProfile {
+    /// Only png and json files, ACC regenerates the dds files
+    Minimal,
+    /// Every file in the livery folder
+    Full
+}
+
+impl From<ExportProfile> for export::ExportProfile {
+    fn from(profile: ExportProfile) -> Self {
+        match profile {
+            ExportProfile::Minimal => export::ExportProfile::Minimal,
+            ExportProfile::Full => export::ExportProfile::Full
+        }
+    }
+}
+
+/// Operations recorded in the history
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Operation {
+    Install,
+    Export,
+    Delete,
+    Rename,
+    Relink,
+    Merge,
+    Clean,
+    Undo,
+    LiveryMode
+}
+
+impl From<Operation> for history::Operation {
+    fn from(operation: Operation) -> Self {
+        match operation {
+            Operation::Install => history::Operation::Install,
+            Operation::Export => history::Operation::Export,
+            Operation::Delete => history::Operation::Delete,
+            Operation::Rename => history::Operation::Rename,
+            Operation::Relink => history::Operation::Relink,
+            Operation::Merge => history::Operation::Merge,
+            Operation::Clean => history::Operation::Clean,
+            Operation::Undo => history::Operation::Undo,
+            Operation::LiveryMode => history::Operation::LiveryMode
+        }
+    }
+}
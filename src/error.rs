@@ -0,0 +1,82 @@
+use std::{fmt::Display, io};
+
+/// Everything that can go wrong, Display gives the message shown to the user
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Zip(zip::result::ZipError),
+    /// A json file could not be parsed or written
+    Json(String),
+    /// Documents/Assetto Corsa Competizione (with a Customs folder) was not found
+    AccFolderNotFound,
+    /// A config file of ACC misses a key this tool has to change
+    MissingConfigKey { file: String, key: String },
+    /// Files already exist and the conflict was not resolved
+    Conflict(String),
+    /// A livery, car.json or file that was asked for doesn't exist
+    NotFound(String),
+    /// An archive or folder that can't be imported
+    InvalidArchive(String),
+    /// A command line argument that can't be used (checked beyond what the argument parser does)
+    InvalidArgument(String)
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// Exit code of the process when aborting with this error
+    /// 2 ACC folder not found, 3 not found, 4 conflict, 5 IO, 6 archive, 7 json/config, 8 invalid argument
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::AccFolderNotFound => 2,
+            Error::NotFound(_) => 3,
+            Error::Conflict(_) => 4,
+            Error::Io(_) => 5,
+            Error::Zip(_) | Error::InvalidArchive(_) => 6,
+            Error::Json(_) | Error::MissingConfigKey { .. } => 7,
+            Error::InvalidArgument(_) => 8
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Zip(e) => write!(f, "failed to read zip file: {}", e),
+            Error::Json(msg) => write!(f, "invalid json: {}", msg),
+            Error::AccFolderNotFound => write!(f, "Documents/Assetto Corsa Competizione folder not found\nMake sure you installed the game and ran it at least once"),
+            Error::MissingConfigKey { file, key } => write!(f, "{} has no {}, start ACC once to let it write its settings", file, key),
+            Error::Conflict(msg) => write!(f, "{}", msg),
+            Error::NotFound(what) => write!(f, "{} not found", what),
+            Error::InvalidArchive(msg) => write!(f, "{}", msg),
+            Error::InvalidArgument(msg) => write!(f, "{}", msg)
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Self {
+        Error::Zip(e)
+    }
+}
+
+impl From<json::Error> for Error {
+    fn from(e: json::Error) -> Self {
+        Error::Json(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e.to_string())
+    }
+}
@@ -0,0 +1,52 @@
+//! Managing the custom liveries of Assetto Corsa Competizione
+//!
+//! Everything works on a [`State`], pointing to the ACC documents folder (the one containing Customs and Config),
+//! so it can be used with any folder, e.g. a copy of it for testing.
+//! The main entry points are:
+//! - [`backend::install::install`] installs an archive or folder
+//! - [`backend::export::resolve`] and [`backend::livery_ops::write_livery_in_zip`] export a livery
+//! - [`backend::library::get_installed_liveries`] lists the installed liveries
+//! - [`backend::app_data::toggle_liverymode`] switches livery mode
+
+use std::path::{Path, PathBuf};
+
+use error::{Error, Result};
+
+pub mod backend;
+pub mod error;
+pub mod model;
+
+/// The ACC documents folder all operations work on
+#[derive(Debug, Clone)]
+pub struct State {
+    root_folder: PathBuf
+}
+
+impl State {
+    /// Uses this folder as ACC documents folder, it is not checked if it exists
+    pub fn new(root_folder: impl Into<PathBuf>) -> State {
+        State { root_folder: root_folder.into() }
+    }
+
+    /// Uses this folder as ACC documents folder, if it contains a Customs folder
+    pub fn open(root_folder: impl Into<PathBuf>) -> Result<State> {
+        let root_folder = std::path::absolute(root_folder.into())?;
+        if !root_folder.join(backend::livery_ops::ACC_CUSTOMS_FOLDER_NAME).is_dir() {
+            return Err(Error::NotFound(format!("{} folder in {}", backend::livery_ops::ACC_CUSTOMS_FOLDER_NAME, root_folder.display())));
+        }
+
+        Ok(State::new(root_folder))
+    }
+
+    /// Finds the ACC documents folder of the installed game (through Steam/Proton on Linux)
+    pub fn detect() -> Result<State> {
+        match backend::get_acc_folder() {
+            Ok(Some(folder)) | Err(Some(folder)) => Ok(State::new(folder)),
+            _ => Err(Error::AccFolderNotFound)
+        }
+    }
+
+    pub fn root_folder(&self) -> &Path {
+        &self.root_folder
+    }
+}
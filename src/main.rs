@@ -1,14 +1,13 @@
 use std::path::PathBuf;
 
-use backend::livery_ops;
+use acc_skinmanager::{State, backend::{self, livery_ops}, error::{Error, Result}};
 use clap::Parser;
-use dialoguer::{Confirm, Input};
+use dialoguer::{Confirm, Input, Select};
 use indicatif::{ProgressBar, ProgressStyle};
 
-use crate::backend::livery_ops::{Livery, Conflict};
+use acc_skinmanager::backend::{livery_ops::{Livery, Conflict, RejectedEntry}, library::{self, LiveryFilter, LiveryListEntry}, orphans::{self, OrphanKind}, duplicates, export::{self, ExportOptions, ExportFilter}, install::{self, ConflictPolicies, InstallOutcome, InstallSummary, FileStatus}, archive::{self, CompressionOptions}, content_index::ContentIndex, transaction::Transaction, tool_config::ToolConfig, backup::{self, JournalEntry}, history::{self, HistoryEntry, HistoryFilter, Operation}, verify::{self, VerifyReport}};
 
-pub mod backend;
-pub mod model;
+mod cli;
 pub mod view;
 
 #[derive(Debug, Parser)]
@@ -17,35 +16,180 @@ struct Args {
     #[arg(short, long, help = "Switches ACC into Liverymode (only use when game is turned off)")]
     mode: bool,
 
-    #[arg(short, long, help = "Installs a zipfile")]
+    #[arg(short, long, help = "Installs an archive (zip, 7z, tar, tar.gz, tar.zst) or folder")]
     install: Option<String>,
 
-    #[arg(short, long, help = "Export livery into zipfile based on car.json name or livery foldername")]
-    export: Option<String>,
+    #[arg(long, help = "installs without checking the archive for damaged files first")]
+    no_verify: bool,
+
+    #[arg(long, value_name = "ARCHIVE", help = "Checks an archive or folder for damaged files (CRC, manifest, car.json, png and dds headers)")]
+    verify: Option<String>,
+
+    #[arg(long, value_enum, help = "resolves install conflicts without asking")]
+    on_conflict: Option<cli::ConflictPolicy>,
+
+    #[arg(long, value_enum, help = "overrides --on-conflict when both car.json and livery folder exist")]
+    on_both_conflict: Option<cli::ConflictPolicy>,
+
+    #[arg(long, value_enum, help = "overrides --on-conflict when only the car.json exists")]
+    on_car_conflict: Option<cli::ConflictPolicy>,
+
+    #[arg(long, value_enum, help = "overrides --on-conflict when only the livery folder exists")]
+    on_livery_conflict: Option<cli::ConflictPolicy>,
+
+    #[arg(short, long, num_args = 1.., help = "Export liveries into archives based on car.json names or livery foldernames, * and ? can be used as wildcards")]
+    export: Vec<String>,
+
+    #[arg(long, help = "Exports all installed liveries (narrowed down by --team and --car-model)")]
+    export_all: bool,
+
+    #[arg(long, value_name = "NAME", help = "exports all selected liveries into one archive with this name, instead of one archive per livery")]
+    combined: Option<String>,
+
+    #[arg(long, value_name = "PATH", help = "folder the archives are exported into (or the file path, if only one archive is exported)")]
+    output: Option<PathBuf>,
+
+    #[arg(long, value_name = "TEMPLATE", default_value = export::DEFAULT_TEMPLATE, help = "naming template of exported archives, with {name}, {folder}, {team}, {number}, {car} and {date}")]
+    name_template: String,
+
+    #[arg(long, value_enum, default_value = "refuse", help = "what to do if the exported archive already exists")]
+    if_exists: cli::IfExists,
+
+    #[arg(long, value_enum, default_value = "deflate", help = "compression method of zip exports")]
+    compression: cli::CompressionMethod,
+
+    #[arg(long, value_name = "EXT=METHOD", value_parser = cli::parse_extension_method, help = "compression method for a file extension in zip exports (png and dds are stored by default), can be repeated")]
+    compress_ext: Vec<(String, archive::CompressionMethod)>,
+
+    #[arg(long, allow_hyphen_values = true, help = "compression level of the export, depends on the method (e.g. 0-9 for deflate, 7z and tar.gz, 1-22 for zstd)")]
+    level: Option<i32>,
+
+    #[arg(long, value_enum, default_value = "full", help = "which livery files are exported, minimal leaves out the dds files ACC regenerates")]
+    profile: cli::ExportProfile,
+
+    #[arg(long, value_name = "PATTERN", help = "only exports livery files matching this pattern (* and ? as wildcards), can be repeated")]
+    include: Vec<String>,
+
+    #[arg(long, value_name = "PATTERN", help = "leaves out livery files matching this pattern (* and ? as wildcards), can be repeated")]
+    exclude: Vec<String>,
 
     #[arg(short = 'O', long, help = "exports only the livery folder")]
     export_only_livery: bool,
 
+    #[arg(long, value_enum, default_value = "zip", help = "archive format of the export")]
+    format: cli::ArchiveFormat,
+
     #[arg(long, help = "opens the Customs folder")]
     open: bool,
 
+    #[arg(short, long, help = "Lists all installed liveries")]
+    list: bool,
+
+    #[arg(long, help = "only liveries of this car model (name or carModelType id)")]
+    car_model: Option<String>,
+
+    #[arg(long, help = "only liveries of this team")]
+    team: Option<String>,
+
+    #[arg(long, help = "prints the output as json")]
+    json: bool,
+
+    #[arg(short, long, help = "Removes dds files the game regenerates from all livery folders")]
+    clean: bool,
+
+    #[arg(long, help = "clean also removes the _1.dds files (generated ingame, takes a while to regenerate)")]
+    clean_ingame_dds: bool,
+
+    #[arg(long, help = "livery folder the clean up skips, can be repeated (in addition to the keep list in settings)")]
+    keep: Vec<String>,
+
+    #[arg(long, help = "Reports car.jsons and livery folders without counterpart, and offers to fix them")]
+    orphans: bool,
+
+    #[arg(long, help = "Finds liveries installed multiple times, and offers to merge them")]
+    duplicates: bool,
+
+    #[arg(long, help = "Shows the history of everything this tool changed")]
+    history: bool,
+
+    #[arg(long, value_enum, help = "history only shows this operation")]
+    operation: Option<cli::Operation>,
+
+    #[arg(long, help = "history only shows entries where a path or the source contains this")]
+    filter: Option<String>,
+
+    #[arg(long, value_name = "YYYY.MM.DD", help = "history only shows entries since this date")]
+    since: Option<String>,
+
+    #[arg(long, help = "history only shows failed operations")]
+    failed: bool,
+
+    #[arg(long, num_args = 0..=1, default_missing_value = "1", value_name = "N", help = "Reverts the last N installs (default 1), restoring the files they replaced")]
+    undo: Option<usize>,
+
+    #[arg(long, help = "only reports what would be done, without changing any files")]
+    dry_run: bool,
+
+    #[arg(long, value_name = "PATH", help = "uses this Assetto Corsa Competizione documents folder (containing Customs) instead of finding it")]
+    acc_dir: Option<PathBuf>,
+
+    #[arg(long, value_name = "PATH", help = "remembers the ACC documents folder to use, instead of finding it on every run")]
+    set_acc_dir: Option<PathBuf>,
+
+    #[arg(long, help = "forgets the folder set with --set-acc-dir, so the ACC folder is found again")]
+    reset_acc_dir: bool,
+
     #[cfg(target_os = "linux")]
     #[arg(long, help = "set the steam root folder manually, instead of using $STEAM_DIR")]
     steam_dir: Option<String>
 }
 
-pub struct State {
-    root_folder: PathBuf
-}
-
 fn main() {
     let args = Args::parse();
-    
+
+    if let Err(e) = run(args) {
+        println!("[ERROR] {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run(args: Args) -> Result<()> {
     #[cfg(target_os = "linux")]
-    if let Some(steam_dir) = args.steam_dir {
+    if let Some(steam_dir) = &args.steam_dir {
         std::env::set_var("STEAM_DIR", steam_dir);
     }
 
+    // Remembering or forgetting the root folder
+    if let Some(folder) = &args.set_acc_dir {
+        let state = State::open(folder)?;
+        let mut config = ToolConfig::load();
+        config.acc_dir = Some(state.root_folder().to_path_buf());
+        config.save()?;
+
+        println!("ACC folder set to {}", state.root_folder().display());
+        return Ok(());
+    }
+
+    if args.reset_acc_dir {
+        let mut config = ToolConfig::load();
+        config.acc_dir = None;
+        config.save()?;
+
+        println!("ACC folder will be found automatically again");
+        return Ok(());
+    }
+
+    // Set folders skip finding the root folder
+    if let Some(folder) = args.acc_dir.clone() {
+        let state = State::open(folder)?;
+        return run_with(args, state);
+    }
+
+    if let Some(folder) = ToolConfig::load().acc_dir {
+        let state = State::open(folder).inspect_err(|_| println!("[WARNING] The ACC folder was set with --set-acc-dir, use --reset-acc-dir to find it automatically again"))?;
+        return run_with(args, state);
+    }
+
     // Getting the root folder
     let res = match backend::get_acc_folder() {
         Ok(res) => res,
@@ -60,95 +204,405 @@ fn main() {
     let acc_settings_folder = if let Some(model) = res {
         model
     } else {
-        println!("[ERROR] Fatal: Documents/Assetto Corsa Competizione folder not found");
-        println!("Make sure you installed the game and ran it at least once");
         // dialog::beep(dialog::BeepType::Error);
         // dialog::alert_default("Documents/Assetto Corsa Competizione folder not found.\n
         //     Make sure you installed the game and ran the game at least once");
-        return;
+        return Err(Error::AccFolderNotFound);
     };
 
-    let state = State {
-        root_folder: acc_settings_folder
-    };
+    run_with(args, State::new(acc_settings_folder))
+}
+
+fn run_with(args: Args, state: State) -> Result<()> {
 
     // Opens the folder in your filemanger
     if args.open {
-        let mut path = state.root_folder;
-        path.push(backend::livery_ops::ACC_CUSTOMS_FOLDER_NAME);
+        let path = state.root_folder().join(livery_ops::ACC_CUSTOMS_FOLDER_NAME);
         if !path.exists() {
-            panic!("ACC Customs folder is missing!");
+            return Err(Error::NotFound("ACC Customs folder".to_string()));
         }
 
-        if let Err(e) = open::that(path) {
-            panic!("Failed to open file path: {}", e);
+        open::that(path)?;
+        return Ok(());
+    }
+
+    // Listing installed liveries
+    if args.list {
+        let filter = LiveryFilter { car_model: args.car_model.clone(), team: args.team.clone() };
+        let entries: Vec<LiveryListEntry> = library::get_installed_liveries(&state).iter()
+            .filter(|liver| filter.matches(liver))
+            .map(|liver| liver.to_list_entry())
+            .collect();
+
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&entries)?);
+        } else {
+            print_livery_table(&entries);
         }
-        return;
+
+        return Ok(());
     }
 
 
-    let mut settings = match backend::app_data::get_settings(&state) {
-        Some(set) => set,
-        None => {
-            panic!("Unable to load app settings, exiting");
+    // Browsing the history
+    if args.history {
+        let since = match args.since.as_ref().map(|date| history::parse_date(date)) {
+            Some(None) => return Err(Error::InvalidArgument(format!("--since has to be a date like {}", chrono::Local::now().format(backend::DATE_FORMAT_STR)))),
+            Some(date) => date,
+            None => None
+        };
+        let filter = HistoryFilter { operation: args.operation.map(Into::into), text: args.filter.clone(), since, failed_only: args.failed };
+        let entries: Vec<HistoryEntry> = history::get_history(&state).into_iter().filter(|entry| filter.matches(entry)).collect();
+
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&entries)?);
+        } else if entries.is_empty() {
+            println!("No history entries found");
+        } else {
+            for entry in entries.iter() {
+                println!("{}  {:<12} {:<7} {}", entry.time, entry.operation.to_string(), if entry.success { "ok" } else { "FAILED" }, entry.outcome);
+                if let Some(source) = &entry.source {
+                    println!("    from {}", source);
+                }
+                for path in entry.paths.iter() {
+                    println!("    {}", path);
+                }
+            }
         }
-    };
 
-    // Switching Livery mode
-    if args.mode {
-        if let Some(mode_state) = settings.switch_liverymode(&state) {
-            println!("Liverymode turned {}", match mode_state {
-                true => "on",
-                false => "off"
+        return Ok(());
+    }
+
+    // Handling orphaned car.jsons and livery folders
+    if args.orphans {
+        let found = orphans::find_orphans(&state);
+        if found.is_empty() {
+            println!("No orphans found");
+            return Ok(());
+        }
+
+        for orphan in found.iter() {
+            println!("{:<22} {}", orphan.kind.to_string(), match &orphan.kind {
+                OrphanKind::UnusedFolder => orphan.livery.get_name(),
+                _ => format!("{} -> {}", orphan.livery.car_file.clone().unwrap_or_default(), orphan.livery.livery_folder.clone().unwrap_or("\"\"".to_string()))
             });
+        }
+        println!("{} orphans found", found.len());
 
-            backend::app_data::write_settings(settings.clone(), &state);
-        } else {
-            panic!("Failed to switch Liverymode");
+        if args.dry_run {
+            return Ok(());
+        }
+
+        for orphan in found {
+            println!("\n{}: {}", orphan.kind, orphan.livery.car_file.clone().unwrap_or(orphan.livery.get_name()));
+
+            let options = ["Keep", "Delete", "Archive into zip and delete", "Re-link"];
+            let choice = Select::new().with_prompt("Action").items(&options).default(0).interact().unwrap_or(0);
+
+            let mut paths = Vec::<String>::new();
+            if let Some(car) = &orphan.livery.car_file {
+                paths.push(format!("{}/{}", livery_ops::ACC_CAR_FOLDER_NAME, car));
+            }
+            if let (OrphanKind::UnusedFolder, Some(folder)) = (&orphan.kind, &orphan.livery.livery_folder) {
+                paths.push(format!("{}/{}", livery_ops::ACC_LIVERY_FOLDER_NAME, folder));
+            }
+
+            let res = match choice {
+                1 => Some((Operation::Delete, orphans::delete(&orphan, &state).map(|_| "Deleted".to_string()))),
+                2 => {
+                    // Logged as an export and a delete, so the history shows where the files went
+                    let res = orphans::archive(&orphan, &state).map(|name| format!("Archived into {}", name));
+                    match &res {
+                        Ok(msg) => println!("{}", msg),
+                        Err(e) => println!("[ERROR] {}", e)
+                    }
+                    let archived = res.is_ok();
+                    log_history(HistoryEntry::new(Operation::Export, paths.clone(), None, res.map_err(|e| e.to_string())), &state);
+
+                    match archived {
+                        true => Some((Operation::Delete, orphans::delete(&orphan, &state).map(|_| "Deleted".to_string()))),
+                        false => continue
+                    }
+                },
+                3 => {
+                    let prompt = match orphan.kind {
+                        OrphanKind::UnusedFolder => "car.json to point to this folder",
+                        _ => "Livery folder to point to"
+                    };
+
+                    match Input::<String>::new().with_prompt(prompt).allow_empty(true).interact_text() {
+                        Ok(target) if !target.is_empty() => Some((Operation::Relink, orphans::relink(&orphan, &target, &state).map(|_| format!("Re-linked to {}", target)))),
+                        _ => None
+                    }
+                },
+                _ => None
+            };
+
+            match res {
+                Some((operation, res)) => {
+                    match &res {
+                        Ok(msg) => println!("{}", msg),
+                        Err(e) => println!("[ERROR] {}", e)
+                    }
+                    log_history(HistoryEntry::new(operation, paths, None, res.map_err(|e| e.to_string())), &state);
+                },
+                None => println!("SKIP")
+            }
         }
 
-        return;
+        return Ok(());
+    }
+
+    // Finding duplicate liveries
+    if args.duplicates {
+        let mut index = ContentIndex::load(&state);
+        let found = duplicates::find_duplicates(&state, &mut index);
+        save_index(&mut index, &state);
+
+        if found.is_empty() {
+            println!("No duplicates found");
+            return Ok(());
+        }
+
+        for group in found.iter() {
+            println!("\n{}", group.folders.join(", "));
+            if !group.same_content {
+                println!("    (folder content differs, only the car.jsons are identical)");
+            }
+            for car in group.car_files.iter() {
+                if group.duplicate_car_files.contains(car) {
+                    println!("    {} (duplicate)", car);
+                } else {
+                    println!("    {}", car);
+                }
+            }
+        }
+        println!("\n{} groups of duplicates found", found.len());
+
+        if args.dry_run {
+            return Ok(());
+        }
+
+        for group in found {
+            println!("\n{}", group.folders.join(", "));
+
+            let mut options: Vec<String> = group.folders.iter().map(|folder| format!("Merge into {}", folder)).collect();
+            options.push("Keep all".to_string());
+            let choice = Select::new().with_prompt("Action").items(&options).default(options.len() - 1).interact().unwrap_or(options.len() - 1);
+
+            if let Some(keep) = group.folders.get(choice) {
+                let res = duplicates::merge(&group, keep, &state).map(|_| format!("Merged into {}", keep)).map_err(|e| e.to_string());
+                match &res {
+                    Ok(msg) => println!("{}", msg),
+                    Err(e) => println!("[ERROR] {}", e)
+                }
+
+                let paths = group.folders.iter().map(|folder| format!("{}/{}", livery_ops::ACC_LIVERY_FOLDER_NAME, folder))
+                    .chain(group.car_files.iter().map(|car| format!("{}/{}", livery_ops::ACC_CAR_FOLDER_NAME, car)))
+                    .collect();
+                log_history(HistoryEntry::new(Operation::Merge, paths, None, res), &state);
+            } else {
+                println!("SKIP");
+            }
+        }
+
+        return Ok(());
+    }
+
+
+    // Checking an archive
+    if let Some(fil) = args.verify {
+        let report = verify::verify(&PathBuf::from(&fil));
+        print_verify_report(&fil, &report)?;
+
+        return Ok(());
+    }
+
+    // Reverting installs
+    if let Some(count) = args.undo {
+        let entries = backup::get_undoable(count, &state)?;
+        if entries.is_empty() {
+            println!("Nothing to undo");
+            return Ok(());
+        }
+
+        for entry in entries {
+            println!("#{} {} {} ({} created, {} replaced)", entry.id, entry.time, entry.source, entry.created.len(), entry.replaced.len());
+            if args.dry_run {
+                continue;
+            }
+
+            let paths = entry.created.iter().chain(entry.replaced.iter()).cloned().collect();
+            let res = backup::undo(&entry, &state);
+            log_history(HistoryEntry::new(Operation::Undo, paths, entry.backup.clone(), match &res {
+                Ok(_) => Ok(format!("Undone install #{}", entry.id)),
+                Err(e) => Err(e.to_string())
+            }), &state);
+
+            if let Err(e) = res {
+                println!("[ERROR] Failed to undo #{}", entry.id);
+                return Err(e);
+            }
+            println!("Undone");
+        }
+
+        return Ok(());
+    }
+
+    let settings = backend::app_data::get_settings(&state)?;
+
+    // Cleaning up dds files
+    if args.clean {
+        let mut keep_list = settings.get_clean_keep_list().clone();
+        keep_list.extend(args.keep.iter().cloned());
+
+        let entries = backend::cleanup::scan(&state, args.clean_ingame_dds, &keep_list);
+        if entries.is_empty() {
+            println!("Nothing to clean up");
+            return Ok(());
+        }
+
+        let total: u64 = entries.iter().map(|entry| entry.size).sum();
+        for entry in entries.iter() {
+            println!("{:>10}  {} ({} files)", backend::format_size(entry.size), entry.livery_folder, entry.files.len());
+        }
+        println!("{} reclaimable in {} liveries", backend::format_size(total), entries.len());
+
+        if args.dry_run {
+            println!("Dry run, no files were removed");
+            return Ok(());
+        }
+
+        for entry in entries.iter() {
+            let res = backend::cleanup::remove(entry);
+            if let Err(e) = &res {
+                println!("[ERROR] Failed to clean {}: {}", entry.livery_folder, e);
+            }
+
+            let paths = entry.files.iter().map(|file| format!("{}/{}/{}", livery_ops::ACC_LIVERY_FOLDER_NAME, entry.livery_folder, backend::get_filename(file))).collect();
+            log_history(HistoryEntry::new(Operation::Clean, paths, None, res.map(|_| format!("Removed {}", backend::format_size(entry.size))).map_err(|e| e.to_string())), &state);
+        }
+        println!("Finished!");
+
+        return Ok(());
+    }
+
+    // Switching Livery mode
+    if args.mode {
+        match backend::app_data::toggle_liverymode(&state) {
+            Ok(mode_state) => {
+                let mode = match mode_state {
+                    true => "on",
+                    false => "off"
+                };
+                println!("Liverymode turned {}", mode);
+
+                log_history(HistoryEntry::new(Operation::LiveryMode, Vec::new(), None, Ok(format!("Turned {}", mode))), &state);
+            },
+            Err(e) => {
+                log_history(HistoryEntry::new(Operation::LiveryMode, Vec::new(), None, Err(format!("Failed to switch: {}", e))), &state);
+                return Err(e);
+            }
+        }
+
+        return Ok(());
     }
 
     // Installing Zipfile 
     if let Some(fil) = args.install {
         println!("Import...");
         let path = PathBuf::from(fil);
+        let policies = ConflictPolicies {
+            both: args.on_both_conflict.or(args.on_conflict).map(Into::into),
+            car_only: args.on_car_conflict.or(args.on_conflict).map(Into::into),
+            livery_only: args.on_livery_conflict.or(args.on_conflict).map(Into::into)
+        };
+
+        // The files are verified while they are extracted
         println!("Extracting files...");
-        if let Some(val) = livery_ops::get_zip_content(&path) {
-            let results = livery_ops::group_up(val);
+        let source = path.to_string_lossy().to_string();
+        let progressbar = get_progressbar("[{elapsed_precise}] {bar:50.cyan/blue} {pos:>1}/{len:5}");
+        let res = livery_ops::get_import_content(&path, &state, &progressbar);
+        progressbar.finish();
+
+        // A dry run doesn't write anything, not even the history or the index
+        let val = match res {
+            Ok(val) => val,
+            Err(e) => {
+                if !args.dry_run {
+                    log_history(HistoryEntry::new(Operation::Install, Vec::new(), Some(source), Err(e.to_string())), &state);
+                }
+                return Err(e);
+            }
+        };
 
-            println!("Installing Liveries from archive {}", path.to_str().expect("it has to exist, else there is no path"));
-            //println!("{} liveries found\n", results.len());
+        if !args.no_verify {
+            if let Err(e) = print_verify_report(&source, &val.verify) {
+                if !args.dry_run {
+                    log_history(HistoryEntry::new(Operation::Install, Vec::new(), Some(source), Err(format!("Verification failed, {} bad entries", val.verify.issues.len()))), &state);
+                }
+                println!("Nothing was installed, use --no-verify to skip this check");
+                return Err(e);
+            }
+        }
+        print_rejected(&val.rejected);
+
+        // Nothing to ask, so the library does the whole install
+        if policies.is_complete() && !args.dry_run {
+            let report = match install::install_content(val, &source, &policies, &state) {
+                Ok(report) => report,
+                Err(e) => {
+                    log_history(HistoryEntry::new(Operation::Install, Vec::new(), Some(source), Err(e.to_string())), &state);
+                    return Err(e);
+                }
+            };
 
-            let progressbar = ProgressBar::new(results.len() as u64);
-            progressbar.set_style(ProgressStyle::with_template("[{elapsed_precise}] {bar:50.cyan/blue} {pos:>1}/{len:5} {msg}")
-                    .expect("Progress Style is valid (At least when it was typed, an update to indicatif might have broken it)")
-                    .progress_chars("##-"));
-            //return;
+            println!("Installed Liveries from {}", path.display());
+            finish_install(&report.summary, report.journal, source, &state);
+            return Ok(());
+        }
 
-            for item in results {
-                progressbar.set_message(if let Some(liver) = item.livery_folder.clone() {
-                    liver
-                } else {
-                    if let Some(car) = &item.car_json {
-                        car.name.clone()
-                    } else {
-                        String::new()
-                    }
-                });
-                
-                
-                fn handle_write(liver: &Livery, state: &State) {
-                    if let Err(e) = liver.write(state) {
-                        panic!("Error occured when writing file: {}", e);
-                    }
-                }
+        let results = livery_ops::group_content(val.files, val.manifest.as_ref());
+        let mut index = ContentIndex::load(&state);
+
+        if args.dry_run {
+            print_install_plan(&results, &policies, &state, &mut index);
+            return Ok(());
+        }
+
+        if let Err(e) = install::check_fail_policy(&results, &policies, &state, &mut index) {
+            log_history(HistoryEntry::new(Operation::Install, Vec::new(), Some(source), Err(e.to_string())), &state);
+            save_index(&mut index, &state);
+            return Err(e);
+        }
+
+        println!("Installing Liveries from {}", path.display());
+        //println!("{} liveries found\n", results.len());
+
+        let progressbar = get_progressbar("[{elapsed_precise}] {bar:50.cyan/blue} {pos:>1}/{len:5} {msg}");
+        progressbar.set_length(results.len() as u64);
+        //return;
 
-                match item.check_if_conflict(&state) {
+        let mut summary = InstallSummary::default();
+        let mut transaction = Transaction::begin(&state)?;
+
+        for item in results {
+            let name = item.get_name();
+            progressbar.set_message(name.clone());
+            
+            
+            fn handle_write(liver: &Livery, state: &State, transaction: &mut Transaction, outcome: InstallOutcome) -> Result<InstallOutcome> {
+                liver.write(state, transaction).map(|_| outcome)
+            }
+
+            let conflict = item.check_if_conflict(&state, &mut index);
+            let outcome = if let Some(policy) = policies.get(&conflict) {
+                install::apply_policy(item, &conflict, policy, &state, &mut transaction)
+            } else {
+                match conflict {
                     Conflict::None => {
                         // No conflict, continue
-                        handle_write(&item, &state);
+                        handle_write(&item, &state, &mut transaction, InstallOutcome::Written)
                     },
                     Conflict::Both => {
                         //Both conflict, so offer override
@@ -157,9 +611,10 @@ fn main() {
                         item.livery_folder.clone().expect("has to exist to conflict"));
 
                         if Confirm::new().with_prompt("Override?").default(true).interact().unwrap_or(false) {
-                            handle_write(&item, &state);
+                            handle_write(&item, &state, &mut transaction, InstallOutcome::Written)
                         } else {
                             println!("SKIP");
+                            Ok(InstallOutcome::Skipped)
                         }
                     },
                     Conflict::CarOnly => {
@@ -173,10 +628,7 @@ fn main() {
                         filename.set_extension("");
                         let mut filename = filename.to_str().expect("there has to be a filename").to_string();
                         
-                        let mut base_folder = state.root_folder.clone();
-                        base_folder.push(backend::livery_ops::ACC_CUSTOMS_FOLDER_NAME);
-                        base_folder.push(backend::livery_ops::ACC_CAR_FOLDER_NAME);
-                        let base_folder = base_folder;
+                        let base_folder = library::get_cars_folder(&state);
                         
                         let mut target = base_folder.clone();
                         target.push(&filename);
@@ -205,109 +657,334 @@ fn main() {
                         // Editing the car.json
                         if !skip {
                             car.name = backend::get_filename(&target);
+                            let new_name = car.name.clone();
                             let alt = Livery {car_json: Some(car), livery_folder: None, livery_files: Vec::<backend::livery_ops::ZipLiveryContent>::new()};
-                            handle_write(&alt, &state);
-                            handle_write(&item, &state); // Item no longer has a car_json, so no more conflict
+                            // Item no longer has a car_json, so no more conflict
+                            handle_write(&alt, &state, &mut transaction, InstallOutcome::Written)
+                                .and_then(|_| handle_write(&item, &state, &mut transaction, InstallOutcome::Renamed(new_name)))
                         } else {
                             println!("SKIP");
+                            Ok(InstallOutcome::Skipped)
                         }
                     },
                     Conflict::LiveryOnly => {
                         println!("Conflict\nLivery folder {} already exist", item.livery_folder.clone().expect("has to exist to conflict"));
 
                         if Confirm::new().with_prompt("Override?").default(true).interact().unwrap_or(false) {
-                            handle_write(&item, &state);
+                            handle_write(&item, &state, &mut transaction, InstallOutcome::Written)
                         } else {
                             println!("SKIP");
+                            Ok(InstallOutcome::Skipped)
                         }
                     },
                     Conflict::Identical => {
                         println!("Already Up-to-date, SKIP");
+                        Ok(InstallOutcome::Identical)
                     }
                 }
+            };
 
-                progressbar.inc(1);
+            let error = match outcome {
+                Ok(InstallOutcome::Failed) => Error::Conflict(format!("Conflict on {}, aborting install", name)),
+                Ok(outcome) => {
+                    summary.add(name, &outcome);
+                    progressbar.inc(1);
+                    continue;
+                },
+                Err(e) => {
+                    println!("[ERROR] Failed to write {}", name);
+                    e
+                }
+            };
+
+            // Nothing of this install is kept, as nothing was moved into place yet only the staged files are removed
+            progressbar.abandon();
+            let count = transaction.get_staged_count();
+            match transaction.rollback() {
+                Ok(_) => println!("Discarded {} staged files, nothing was installed", count),
+                Err(e) => println!("[WARNING] Failed to remove the staged files: {}", e)
             }
-            progressbar.set_message("DONE");
-            progressbar.finish();
-        } else {
-            panic!("Failed to read zip file");
-        }
+            save_index(&mut index, &state);
 
-        println!("Finished!");
+            log_history(HistoryEntry::new(Operation::Install, Vec::new(), Some(source), Err(format!("{}, rolled back", error))), &state);
+            return Err(error);
+        }
+        progressbar.set_message("DONE");
+        progressbar.finish();
+        let journal = match transaction.commit(&source, &state) {
+            Ok(journal) => journal,
+            Err(e) => {
+                save_index(&mut index, &state);
+                log_history(HistoryEntry::new(Operation::Install, Vec::new(), Some(source), Err(format!("{}, rolled back", e))), &state);
+                return Err(e);
+            }
+        };
+        save_index(&mut index, &state);
+        finish_install(&summary, journal, source, &state);
 
-        return;
+        return Ok(());
     }
 
     //Extract Liveryfile
-    if let Some(name) = args.export {
+    if !args.export.is_empty() || args.export_all {
         println!("Export...");
-        print!("Trying to find {}... ", &name);
 
-        let bundle = if let Some(car) = livery_ops::get_car_file(&name, &state) {
-            let (folder, content) = if let Some(folder) = livery_ops::read_car_for_livery_folder(&car) {
-                if let Some(content) = livery_ops::get_livery_files(&folder, &state) {
-                    (Some(folder), content)
-                } else {
-                    (None, Vec::<livery_ops::ZipLiveryContent>::new())
-                }
-            } else {
-                (None, Vec::<livery_ops::ZipLiveryContent>::new())
-            };
+        let names = if args.export_all {
+            export::select_by_filter(&LiveryFilter { car_model: args.car_model.clone(), team: args.team.clone() }, &state)
+        } else {
+            let (names, unmatched) = export::select_by_names(&args.export, &state);
+            for pattern in unmatched {
+                println!("[WARNING] {} matched no livery", pattern);
+            }
+            names
+        };
 
-            // Handling export flag
-            if !args.export_only_livery {
-                Livery {car_json: Some(car), livery_folder: folder, livery_files: content}
-            } else {
-                Livery {car_json: None, livery_folder: folder, livery_files: content}
+        let filter = ExportFilter { profile: args.profile.into(), include: args.include.clone(), exclude: args.exclude.clone() };
+        let mut bundles = Vec::<Livery>::new();
+        for name in names.iter() {
+            print!("Trying to find {}... ", name);
+
+            match export::resolve(name, args.export_only_livery, &state).map(|mut bundle| {
+                filter.apply(&mut bundle);
+                bundle
+            }) {
+                // Odd case when car.json is found, but not included due to only exporting livery files
+                Ok(bundle) if bundle.car_json.is_none() && bundle.livery_files.is_empty() => println!("exists as a car.json, but no livery files could be found, SKIP"),
+                Ok(bundle) => {
+                    println!("FOUND!");
+                    bundles.push(bundle);
+                },
+                Err(e) => println!("{}, SKIP", e)
             }
-        } else if let Some(content) = livery_ops::get_livery_files(&name, &state) {
-            //Challenge: we need to find the car_json that contains our folder
+        }
 
-            //Except when we don't:
-            if args.export_only_livery {
-                Livery {car_json: None, livery_folder: Some(name.clone()), livery_files: content}
-            } else {
-                let all_cars = livery_ops::get_all_car_json(&state);
+        if bundles.is_empty() {
+            return Err(Error::NotFound("Any of the liveries to export".to_string()));
+        }
 
-                let mut car = None;
+        let mut compression = CompressionOptions { method: args.compression.into(), level: args.level, ..CompressionOptions::default() };
+        compression.per_extension.extend(args.compress_ext.iter().cloned());
 
-                // We go through all car.json to find one which points to this folder 
-                for item in all_cars {
-                    if let Some(folder) = livery_ops::read_car_for_livery_folder(&item) {
-                        if folder == name {
-                            car = Some(item);
-                            break;
-                        }
-                    }
+        let format = args.format.into();
+        let options = ExportOptions { output: args.output.clone(), template: args.name_template.clone(), if_exists: args.if_exists.into() };
+        if options.is_file_output() && args.combined.is_none() && bundles.len() > 1 {
+            return Err(Error::InvalidArgument(format!("--output is a file path, but {} archives would be exported. Use a folder (ending with a path separator) or --combined", bundles.len())));
+        }
+
+        //Completing the export
+        fn finish_export(res: Result<String>, paths: Vec<String>, state: &State) -> Result<()> {
+            match res {
+                Ok(target_name) => {
+                    println!("Exported {} successfully!", target_name);
+                    log_history(HistoryEntry::new(Operation::Export, paths, None, Ok(format!("Exported into {}", target_name))), state);
+                    Ok(())
+                },
+                Err(e) => {
+                    log_history(HistoryEntry::new(Operation::Export, paths, None, Err(e.to_string())), state);
+                    Err(e)
                 }
-                
-                Livery {car_json: car, livery_folder: Some(name.clone()), livery_files: content }
             }
-        } else {
-            panic!("No file or folder found!");
-        };
-
-        println!("FOUND!");
+        }
 
-        if bundle.car_json.is_none() && bundle.livery_files.is_empty() {
-            // Odd case when car.json is found, but not included due to only exporting livery files
-            panic!("{} exists as a car.json, but no livery files could be found, exiting", name);
+        fn get_paths(bundle: &Livery) -> Vec<String> {
+            bundle.car_json.iter().chain(bundle.livery_files.iter()).map(|item| item.get_interal_path()).collect()
         }
 
+        println!("Compressing Files...");
+        let progressbar = get_progressbar("[{elapsed_precise}] {bar:50.cyan/blue} {pos:>1}/{len:5}");
 
-        //Completing the export
-        if let Ok(target_name) = livery_ops::write_livery_in_zip(bundle) {
-            println!("Exported {} successfully!", target_name);
-        } else {
-            panic!("Error while trying to create zip file");
+        if let Some(pack) = &args.combined {
+            let paths = bundles.iter().flat_map(get_paths).collect();
+            let res = options.get_target(pack, format)
+                .and_then(|target| livery_ops::write_liveries_in_zip(bundles, &target, format, &compression, &progressbar).map(|_| target.display().to_string()));
+            progressbar.finish();
+            return finish_export(res, paths, &state);
+        }
+
+        // The other archives are still exported if one fails, the last error is returned
+        let mut failed = None;
+        for bundle in bundles {
+            let paths = get_paths(&bundle);
+            let res = options.get_target(&export::apply_template(&options.template, &bundle), format)
+                .and_then(|target| livery_ops::write_livery_in_zip(bundle, &target, format, &compression, &progressbar).map(|_| target.display().to_string()));
+            if let Err(e) = finish_export(res, paths, &state) {
+                if let Some(previous) = failed.replace(e) {
+                    println!("[ERROR] Error while trying to create archive: {}", previous);
+                }
+            }
         }
 
-        
-        return;
+        progressbar.finish();
+
+        return failed.map_or(Ok(()), Err);
     }
 
 
     //Graphic app
-    
+
+    Ok(())
+}
+
+fn print_livery_table(entries: &[LiveryListEntry]) {
+    let rows: Vec<[String; 7]> = entries.iter().map(|entry| [
+        entry.file_name.clone().unwrap_or("-".to_string()),
+        entry.team_name.clone().unwrap_or("-".to_string()),
+        entry.race_number.map(|num| format!("#{}", num)).unwrap_or("-".to_string()),
+        entry.car_model.clone().unwrap_or("-".to_string()),
+        entry.livery_folder.clone().unwrap_or("-".to_string()),
+        entry.file_count.to_string(),
+        backend::format_size(entry.total_size)
+    ]).collect();
+
+    let header = ["File", "Team", "Number", "Car", "Livery Folder", "Files", "Size"];
+    let mut widths = header.map(|title| title.chars().count());
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let print_row = |row: &[String]| {
+        let line: Vec<String> = row.iter().zip(widths.iter()).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+
+    print_row(&header.map(|title| title.to_string()));
+    for row in rows.iter() {
+        print_row(row);
+    }
+
+    println!("\n{} liveries, {} total", entries.len(), backend::format_size(entries.iter().map(|entry| entry.total_size).sum()));
+}
+
+/// Adds the entry to the history, failing to do so is not worth aborting for
+fn log_history(entry: HistoryEntry, state: &State) {
+    if let Err(e) = history::record(&entry, state) {
+        println!("[WARNING] Failed to write history: {}", e);
+    }
+}
+
+/// Drops entries of removed files and writes the content index, failing to do so only costs speed next time
+fn save_index(index: &mut ContentIndex, state: &State) {
+    index.prune(state);
+    if let Err(e) = index.save(state) {
+        println!("[WARNING] Failed to write content index: {}", e);
+    }
+}
+
+fn print_install_plan(results: &[Livery], policies: &ConflictPolicies, state: &State, index: &mut ContentIndex) {
+    let mut total_delta = 0;
+
+    for item in results {
+        let plan = install::plan(item, state, index);
+
+        println!("\n{}", plan.name);
+        match (&plan.car_json, &plan.livery_folder) {
+            (Some(car), Some(folder)) => println!("    {} -> {}", car, folder),
+            (Some(car), None) => println!("    {} -> no livery folder", car),
+            (None, _) => println!("    no car.json")
+        }
+
+        println!("    Conflict: {:?}{}", plan.conflict, match policies.get(&plan.conflict) {
+            Some(policy) => format!(" (resolved with {:?})", policy),
+            None if plan.conflict != Conflict::None && plan.conflict != Conflict::Identical => " (would ask)".to_string(),
+            None => String::new()
+        });
+
+        for file in plan.files.iter() {
+            let status = match file.status {
+                FileStatus::New => "new",
+                FileStatus::Identical => "identical",
+                FileStatus::Overwritten => "overwrite"
+            };
+            let delta = file.size_delta();
+            total_delta += delta;
+
+            println!("    {:<10} {} ({}{})", status, file.internal_path, if delta < 0 { "-" } else { "+" }, backend::format_size(delta.unsigned_abs()));
+        }
+    }
+
+    println!("\n{} liveries, {}{} on disk", results.len(), if total_delta < 0 { "-" } else { "+" }, backend::format_size(total_delta.unsigned_abs()));
+    println!("Dry run, no files were written");
+}
+
+/// Empty progressbar with the style used everywhere, the length is set by whatever advances it
+fn get_progressbar(template: &str) -> ProgressBar {
+    let progressbar = ProgressBar::new(0);
+    progressbar.set_style(ProgressStyle::with_template(template)
+            .expect("Progress Style is valid (At least when it was typed, an update to indicatif might have broken it)")
+            .progress_chars("##-"));
+
+    progressbar
+}
+
+/// Warns about the entries that were not imported
+fn print_rejected(rejected: &[RejectedEntry]) {
+    if !rejected.is_empty() {
+        println!("[WARNING] {} entries were rejected, as they would be written outside of the Customs folder or are damaged:", rejected.len());
+        for entry in rejected.iter() {
+            println!("    {} ({})", entry.entry, entry.reason);
+        }
+    }
+}
+
+/// Logs a committed install into the history and prints the summary
+fn finish_install(summary: &InstallSummary, journal: Option<JournalEntry>, source: String, state: &State) {
+    let paths = match journal {
+        Some(entry) => {
+            if let Some(backup) = &entry.backup {
+                println!("Replaced files were backed up into {}", backup);
+            }
+            entry.created.into_iter().chain(entry.replaced).collect()
+        },
+        None => Vec::new()
+    };
+
+    for (old, new) in summary.renamed.iter() {
+        log_history(HistoryEntry::new(Operation::Rename, vec![old.clone()], Some(source.clone()), Ok(format!("Installed as {}", new))), state);
+    }
+    log_history(HistoryEntry::new(Operation::Install, paths, Some(source), Ok(format!("{} written, {} renamed, {} skipped, {} up-to-date",
+        summary.written.len(), summary.renamed.len(), summary.skipped.len(), summary.identical.len()))), state);
+
+    print_summary(summary);
+    println!("Finished!");
+}
+
+fn print_summary(summary: &InstallSummary) {
+    println!("\nSummary:");
+    println!("Written: {}", summary.written.len());
+    for name in summary.written.iter() {
+        println!("    {}", name);
+    }
+
+    println!("Renamed: {}", summary.renamed.len());
+    for (old, new) in summary.renamed.iter() {
+        println!("    {} -> {}", old, new);
+    }
+
+    println!("Skipped: {}", summary.skipped.len());
+    for name in summary.skipped.iter() {
+        println!("    {}", name);
+    }
+
+    println!("Already up-to-date: {}", summary.identical.len());
+}
+
+/// Prints the bad entries, Err if there are any
+fn print_verify_report(source: &str, report: &VerifyReport) -> Result<()> {
+    for issue in report.issues.iter() {
+        if issue.entry.is_empty() {
+            println!("    {}: {}", source, issue.reason);
+        } else {
+            println!("    {}: {}", issue.entry, issue.reason);
+        }
+    }
+
+    let manifest = if report.has_manifest { ", checked against the manifest" } else { "" };
+    if report.is_ok() {
+        println!("{}: {} files ok{}", source, report.checked, manifest);
+        Ok(())
+    } else {
+        Err(Error::InvalidArchive(format!("{}: {} problems found in {} files{}", source, report.issues.len(), report.checked, manifest)))
+    }
 }
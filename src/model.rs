@@ -0,0 +1,343 @@
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+
+/// Text encoding of a json file, kept so rewriting the file doesn't change it
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    /// As written by ACC
+    Utf16Le,
+    Utf16LeBom
+}
+
+/// Typed representation of a car.json within Customs/Cars
+/// Every field ACC writes is mapped, None if the key is missing. Unknown keys and known ones with an unexpected type are kept in `extra`,
+/// together with the key order, the encoding and line endings, so writing it back only changes what was edited
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CarJson {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub car_guid: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team_guid: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub race_number: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub race_number_padding: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aux_light_key: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aux_light_color: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skin_template_key: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skin_color1_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skin_color2_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skin_color3_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sponsor_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skin_material_type1: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skin_material_type2: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skin_material_type3: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rim_color1_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rim_color2_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rim_material_type1: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rim_material_type2: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nationality: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub competitor_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub competitor_nationality: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team_template_key: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub car_model_type: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cup_category: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license_type: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_endurance_kit: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_skin_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub banner_template_key: Option<i32>,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+
+    /// Order of the keys as read, keys added later are written after them
+    #[serde(skip)]
+    pub key_order: Vec<String>,
+    #[serde(skip)]
+    pub encoding: TextEncoding,
+    /// Lines end with \r\n instead of \n
+    #[serde(skip)]
+    pub crlf: bool
+}
+
+impl CarJson {
+    /// Parses a car.json from raw file content
+    /// ACC writes these files in UTF-16LE, but hand edited ones are often UTF-8, so both are accepted
+    pub fn from_bytes(data: &[u8]) -> Option<CarJson> {
+        let (text, encoding) = decode(data);
+        let mut fields = match serde_json::from_str::<Value>(text.as_str()).ok()? {
+            Value::Object(fields) => fields,
+            _ => return None
+        };
+        let key_order = fields.keys().cloned().collect();
+
+        // Known keys with an unexpected type (e.g. "raceNumber": "12") would fail the whole file, so they are kept untyped
+        let untyped: Map<String, Value> = fields.iter()
+            .filter(|(key, value)| serde_json::from_value::<CarJson>(Value::Object(Map::from_iter([((*key).clone(), (*value).clone())]))).is_err())
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        for key in untyped.keys() {
+            fields.remove(key);
+        }
+
+        let mut car: CarJson = serde_json::from_value(Value::Object(fields)).ok()?;
+        car.extra.extend(untyped);
+        car.key_order = key_order;
+        car.encoding = encoding;
+        car.crlf = text.contains("\r\n");
+
+        Some(car)
+    }
+
+    /// Checks if the data is a car.json (and not e.g. a decals.json), by looking for the carModelType key
+    pub fn is_car_json(data: &[u8]) -> bool {
+        match serde_json::from_str::<Value>(decode(data).0.as_str()) {
+            Ok(value) => value.get("carModelType").is_some(),
+            Err(_) => false
+        }
+    }
+
+    /// All keys in the order they were read
+    pub fn to_map(&self) -> Option<Map<String, Value>> {
+        let mut fields = match serde_json::to_value(self).ok()? {
+            Value::Object(fields) => fields,
+            _ => return None
+        };
+
+        let mut output = Map::new();
+        for key in self.key_order.iter() {
+            if let Some(value) = fields.remove(key) {
+                output.insert(key.clone(), value);
+            }
+        }
+        output.extend(fields);
+
+        Some(output)
+    }
+
+    /// Serializes the car.json, indented with 4 spaces like `write_json`, in the encoding it was read in
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        let mut output = Vec::<u8>::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut output, formatter);
+
+        self.to_map()?.serialize(&mut serializer).ok()?;
+
+        let mut text = String::from_utf8(output).ok()?;
+        if self.crlf {
+            text = text.replace('\n', "\r\n");
+        }
+
+        Some(encode(&text, self.encoding))
+    }
+
+    /// The livery folder this car.json points to, None if it uses no custom livery
+    pub fn livery_folder(&self) -> Option<String> {
+        self.custom_skin_name.clone().filter(|folder| !folder.is_empty())
+    }
+
+    /// Points the car.json to another livery folder, empty for none
+    pub fn set_livery_folder(&mut self, folder: &str) {
+        self.extra.remove("customSkinName");
+        self.custom_skin_name = Some(folder.to_string());
+    }
+
+    /// Human readable name of the car model, falls back to the raw id if unknown
+    pub fn car_model_name(&self) -> String {
+        match self.car_model_type {
+            Some(model) => get_car_model_name(model).map(str::to_string).unwrap_or_else(|| format!("Unknown ({})", model)),
+            None => "Unknown".to_string()
+        }
+    }
+}
+
+/// Decodes the text of a json file, which can be either UTF-16LE or UTF-8
+fn decode(data: &[u8]) -> (String, TextEncoding) {
+    if data.starts_with(&[0xFF, 0xFE]) || (data.len() > 1 && data[1] == 0) {
+        let encoding = if data.starts_with(&[0xFF, 0xFE]) { TextEncoding::Utf16LeBom } else { TextEncoding::Utf16Le };
+        let utf16: Vec<u16> = data.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+        let text = String::from_utf16_lossy(utf16.as_slice());
+
+        (text.trim_start_matches('\u{feff}').to_string(), encoding)
+    } else if let Some(rest) = data.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        (String::from_utf8_lossy(rest).to_string(), TextEncoding::Utf8Bom)
+    } else {
+        (String::from_utf8_lossy(data).to_string(), TextEncoding::Utf8)
+    }
+}
+
+fn encode(text: &str, encoding: TextEncoding) -> Vec<u8> {
+    match encoding {
+        TextEncoding::Utf8 => text.as_bytes().to_vec(),
+        TextEncoding::Utf8Bom => [&[0xEF, 0xBB, 0xBF], text.as_bytes()].concat(),
+        TextEncoding::Utf16Le | TextEncoding::Utf16LeBom => {
+            let bom = if encoding == TextEncoding::Utf16LeBom { Some(0xFEFF) } else { None };
+            bom.into_iter().chain(text.encode_utf16()).flat_map(u16::to_le_bytes).collect()
+        }
+    }
+}
+
+/// Resolves the carModelType id to the car name as listed ingame
+pub fn get_car_model_name(car_model_type: i32) -> Option<&'static str> {
+    let name = match car_model_type {
+        // GT3
+        0 => "Porsche 991 GT3 R",
+        1 => "Mercedes-AMG GT3",
+        2 => "Ferrari 488 GT3",
+        3 => "Audi R8 LMS",
+        4 => "Lamborghini Huracan GT3",
+        5 => "McLaren 650S GT3",
+        6 => "Nissan GT-R Nismo GT3 2018",
+        7 => "BMW M6 GT3",
+        8 => "Bentley Continental GT3 2018",
+        9 => "Porsche 991 II GT3 Cup",
+        10 => "Nissan GT-R Nismo GT3 2015",
+        11 => "Bentley Continental GT3 2015",
+        12 => "AMR V12 Vantage GT3",
+        13 => "Reiter Engineering R-EX GT3",
+        14 => "Emil Frey Jaguar G3",
+        15 => "Lexus RC F GT3",
+        16 => "Lamborghini Huracan GT3 Evo",
+        17 => "Honda NSX GT3",
+        18 => "Lamborghini Huracan SuperTrofeo",
+        19 => "Audi R8 LMS Evo",
+        20 => "AMR V8 Vantage",
+        21 => "Honda NSX GT3 Evo",
+        22 => "McLaren 720S GT3",
+        23 => "Porsche 991 II GT3 R",
+        24 => "Ferrari 488 GT3 Evo",
+        25 => "Mercedes-AMG GT3 2020",
+        26 => "Ferrari 488 Challenge Evo",
+        27 => "BMW M2 CS Racing",
+        28 => "Porsche 992 GT3 Cup",
+        29 => "Lamborghini Huracan SuperTrofeo EVO2",
+        30 => "BMW M4 GT3",
+        31 => "Audi R8 LMS GT3 Evo II",
+        32 => "Ferrari 296 GT3",
+        33 => "Lamborghini Huracan GT3 EVO2",
+        34 => "Porsche 992 GT3 R",
+        35 => "McLaren 720S GT3 Evo",
+        36 => "Ford Mustang GT3",
+        // GT4
+        50 => "Alpine A110 GT4",
+        51 => "AMR V8 Vantage GT4",
+        52 => "Audi R8 LMS GT4",
+        53 => "BMW M4 GT4",
+        55 => "Chevrolet Camaro GT4",
+        56 => "Ginetta G55 GT4",
+        57 => "KTM X-Bow GT4",
+        58 => "Maserati MC GT4",
+        59 => "McLaren 570S GT4",
+        60 => "Mercedes-AMG GT4",
+        61 => "Porsche 718 Cayman GT4",
+        // GT2
+        80 => "Audi R8 LMS GT2",
+        82 => "KTM X-Bow GT2",
+        83 => "Maserati MC20 GT2",
+        84 => "Mercedes-AMG GT2",
+        85 => "Porsche 911 GT2 RS CS Evo",
+        86 => "Porsche 935",
+        _ => return None
+    };
+
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A car.json in the layout ACC writes, with a key this tool doesn't know about
+    const ACC_CAR_JSON: &str = "{\r\n    \"carGuid\": -1,\r\n    \"teamGuid\": -1,\r\n    \"raceNumber\": 992,\r\n    \"raceNumberPadding\": 0,\r\n    \"auxLightKey\": 0,\r\n    \"auxLightColor\": 0,\r\n    \"skinTemplateKey\": 99,\r\n    \"skinColor1Id\": 354,\r\n    \"skinColor2Id\": 1,\r\n    \"skinColor3Id\": 1,\r\n    \"sponsorId\": 0,\r\n    \"skinMaterialType1\": 0,\r\n    \"skinMaterialType2\": 0,\r\n    \"skinMaterialType3\": 0,\r\n    \"rimColor1Id\": 1,\r\n    \"rimColor2Id\": 1,\r\n    \"rimMaterialType1\": 2,\r\n    \"rimMaterialType2\": 2,\r\n    \"teamName\": \"Team Iris\",\r\n    \"nationality\": 0,\r\n    \"displayName\": \"\",\r\n    \"competitorName\": \"\",\r\n    \"competitorNationality\": 0,\r\n    \"teamTemplateKey\": 0,\r\n    \"carModelType\": 34,\r\n    \"cupCategory\": 0,\r\n    \"licenseType\": 0,\r\n    \"useEnduranceKit\": 1,\r\n    \"customSkinName\": \"#2_TeamIris_992\",\r\n    \"bannerTemplateKey\": 1,\r\n    \"someFutureKey\": [\r\n        1.5,\r\n        \"x\"\r\n    ]\r\n}";
+
+    fn utf16(text: &str, bom: bool) -> Vec<u8> {
+        let bom = if bom { Some(0xFEFF) } else { None };
+        bom.into_iter().chain(text.encode_utf16()).flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn round_trip_is_lossless() {
+        for data in [utf16(ACC_CAR_JSON, true), utf16(ACC_CAR_JSON, false), ACC_CAR_JSON.as_bytes().to_vec(), ACC_CAR_JSON.replace("\r\n", "\n").into_bytes()] {
+            let car = CarJson::from_bytes(&data).expect("valid car.json");
+            assert_eq!(car.to_bytes().expect("serializable"), data);
+        }
+    }
+
+    #[test]
+    fn fields_are_read() {
+        let car = CarJson::from_bytes(&utf16(ACC_CAR_JSON, true)).expect("valid car.json");
+
+        assert_eq!(car.encoding, TextEncoding::Utf16LeBom);
+        assert_eq!(car.race_number, Some(992));
+        assert_eq!(car.team_name.as_deref(), Some("Team Iris"));
+        assert_eq!(car.skin_color1_id, Some(354));
+        assert_eq!(car.nationality, Some(0));
+        assert_eq!(car.display_name.as_deref(), Some(""));
+        assert_eq!(car.extra.get("someFutureKey"), Some(&serde_json::json!([1.5, "x"])));
+        assert_eq!(car.car_model_name(), "Porsche 992 GT3 R");
+        assert_eq!(car.livery_folder().as_deref(), Some("#2_TeamIris_992"));
+        assert!(CarJson::is_car_json(&utf16(ACC_CAR_JSON, false)));
+    }
+
+    #[test]
+    fn editing_keeps_everything_else() {
+        let mut car = CarJson::from_bytes(&utf16(ACC_CAR_JSON, true)).expect("valid car.json");
+        car.set_livery_folder("other");
+
+        let expected = ACC_CAR_JSON.replace("#2_TeamIris_992", "other");
+        assert_eq!(car.to_bytes().expect("serializable"), utf16(&expected, true));
+    }
+
+    #[test]
+    fn typed_fields_are_written() {
+        let mut car = CarJson::from_bytes(ACC_CAR_JSON.as_bytes()).expect("valid car.json");
+        car.race_number = Some(7);
+        car.skin_color1_id = Some(1);
+
+        let expected = ACC_CAR_JSON.replace("\"raceNumber\": 992", "\"raceNumber\": 7").replace("\"skinColor1Id\": 354", "\"skinColor1Id\": 1");
+        assert_eq!(car.to_bytes().expect("serializable"), expected.as_bytes());
+    }
+
+    #[test]
+    fn missing_and_unexpected_fields_are_kept_as_they_are() {
+        let text = "{\n    \"raceNumber\": \"12\",\n    \"customSkinName\": \"folder\"\n}";
+        let car = CarJson::from_bytes(text.as_bytes()).expect("valid json object");
+
+        assert_eq!(car.race_number, None);
+        assert_eq!(car.car_model_type, None);
+        assert_eq!(car.extra.get("raceNumber"), Some(&Value::String("12".to_string())));
+        assert_eq!(car.to_bytes().expect("serializable"), text.as_bytes());
+    }
+}
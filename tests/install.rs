@@ -0,0 +1,127 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use indicatif::ProgressBar;
+
+use acc_skinmanager::{State, error::Error, backend::{archive::{ArchiveFormat, ArchiveWriter, CompressionOptions}, install::{self, ConflictPolicies, ConflictPolicy}}};
+
+const CAR_JSON: &str = r##"{
+    "carModelType": 34,
+    "raceNumber": 1,
+    "teamName": "Test Team",
+    "customSkinName": "#1_TestTeam"
+}"##;
+
+/// Empty ACC documents folder and a folder for the files to install, both within the temp folder
+fn setup(name: &str) -> (State, PathBuf) {
+    let root = std::env::temp_dir().join(format!("acc_skinmanager-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+
+    let acc = root.join("acc");
+    fs::create_dir_all(acc.join("Customs").join("Cars")).expect("temp folder is writable");
+    fs::create_dir_all(acc.join("Customs").join("Liveries")).expect("temp folder is writable");
+
+    (State::new(acc), root)
+}
+
+fn write_source(folder: &Path, decals: &[u8]) {
+    fs::create_dir_all(folder.join("Cars")).expect("temp folder is writable");
+    fs::create_dir_all(folder.join("Liveries").join("#1_TestTeam")).expect("temp folder is writable");
+
+    fs::write(folder.join("Cars").join("#1_TestTeam.json"), CAR_JSON).expect("temp folder is writable");
+    fs::write(folder.join("Liveries").join("#1_TestTeam").join("decals.png"), decals).expect("temp folder is writable");
+}
+
+fn installed(state: &State, path: &str) -> PathBuf {
+    state.root_folder().join("Customs").join(path)
+}
+
+#[test]
+fn install_folder() {
+    let (state, root) = setup("folder");
+    let source = root.join("source");
+    write_source(&source, b"decals");
+
+    let report = install::install(&source, &ConflictPolicies::default(), &state, &ProgressBar::hidden()).expect("install succeeds");
+
+    assert_eq!(report.summary.written, vec!["#1_TestTeam".to_string()]);
+    assert!(report.rejected.is_empty());
+    assert!(report.journal.is_some());
+    assert_eq!(fs::read_to_string(installed(&state, "Cars/#1_TestTeam.json")).unwrap(), CAR_JSON);
+    assert_eq!(fs::read(installed(&state, "Liveries/#1_TestTeam/decals.png")).unwrap(), b"decals");
+
+    // Installing it again changes nothing
+    let report = install::install(&source, &ConflictPolicies::default(), &state, &ProgressBar::hidden()).expect("install succeeds");
+    assert_eq!(report.summary.identical.len(), 1);
+    assert!(report.journal.is_none());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn install_archive() {
+    let (state, root) = setup("archive");
+    let archive = root.join("pack.zip");
+
+    let mut writer = ArchiveWriter::create(&archive, ArchiveFormat::Zip, &CompressionOptions::default()).expect("temp folder is writable");
+    writer.add_file("Cars/#1_TestTeam.json", CAR_JSON.as_bytes(), None).unwrap();
+    writer.add_file("Liveries/#1_TestTeam/decals.png", b"decals", None).unwrap();
+    writer.add_file("../outside.txt", b"escaping", None).unwrap();
+    writer.finish().unwrap();
+
+    let report = install::install(&archive, &ConflictPolicies::default(), &state, &ProgressBar::hidden()).expect("install succeeds");
+
+    assert_eq!(report.summary.written.len(), 1);
+    assert_eq!(report.rejected.len(), 1);
+    assert!(installed(&state, "Liveries/#1_TestTeam/decals.png").is_file());
+    assert!(!state.root_folder().join("outside.txt").exists());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn install_flat_archive() {
+    let (state, root) = setup("flat");
+    let archive = root.join("Team_X.zip");
+
+    // No Cars/Liveries layout, the car.json is found by its content and decides the livery folder
+    let mut writer = ArchiveWriter::create(&archive, ArchiveFormat::Zip, &CompressionOptions::default()).expect("temp folder is writable");
+    writer.add_file("car.json", CAR_JSON.as_bytes(), None).unwrap();
+    writer.add_file("decals.png", b"decals", None).unwrap();
+    writer.finish().unwrap();
+
+    let report = install::install(&archive, &ConflictPolicies::default(), &state, &ProgressBar::hidden()).expect("install succeeds");
+
+    assert_eq!(report.summary.written, vec!["#1_TestTeam".to_string()]);
+    assert!(installed(&state, "Cars/car.json").is_file());
+    assert_eq!(fs::read(installed(&state, "Liveries/#1_TestTeam/decals.png")).unwrap(), b"decals");
+    assert!(!installed(&state, "Liveries/Team_X").exists());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn conflict_policies() {
+    let (state, root) = setup("conflict");
+    write_source(&root.join("old"), b"old decals");
+    write_source(&root.join("new"), b"new decals");
+    install::install(&root.join("old"), &ConflictPolicies::default(), &state, &ProgressBar::hidden()).expect("install succeeds");
+
+    let policies = |policy| ConflictPolicies { both: Some(policy), car_only: Some(policy), livery_only: Some(policy) };
+    let decals = installed(&state, "Liveries/#1_TestTeam/decals.png");
+
+    // Fail aborts before anything is written
+    let res = install::install(&root.join("new"), &policies(ConflictPolicy::Fail), &state, &ProgressBar::hidden());
+    assert!(matches!(res, Err(Error::Conflict(_))));
+    assert_eq!(fs::read(&decals).unwrap(), b"old decals");
+
+    let report = install::install(&root.join("new"), &policies(ConflictPolicy::Skip), &state, &ProgressBar::hidden()).expect("install succeeds");
+    assert_eq!(report.summary.skipped.len(), 1);
+    assert_eq!(fs::read(&decals).unwrap(), b"old decals");
+
+    let report = install::install(&root.join("new"), &policies(ConflictPolicy::Overwrite), &state, &ProgressBar::hidden()).expect("install succeeds");
+    assert_eq!(report.summary.written.len(), 1);
+    assert_eq!(report.journal.map(|entry| entry.replaced.len()), Some(2));
+    assert_eq!(fs::read(&decals).unwrap(), b"new decals");
+
+    let _ = fs::remove_dir_all(root);
+}